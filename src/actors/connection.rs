@@ -22,10 +22,10 @@ impl Handler<PieceRequested> for ConnectionActor {
     type Result = Result<bool, std::io::Error>;
 
     fn handle(&mut self, msg: PieceRequested, _ctx: &mut Self::Context) -> Self::Result {
-        match download(msg.endpoint.clone(), &msg.info, msg.piece_idx) {
+        match download(msg.endpoint, &msg.info, msg.piece_idx) {
             Ok(piece) => {
                 msg.torrent_actor.do_send(PieceDownloadSuccessfull {
-                    endpoint: msg.endpoint.clone(),
+                    endpoint: msg.endpoint,
                     piece,
                     piece_idx: msg.piece_idx,
                 });
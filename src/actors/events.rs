@@ -0,0 +1,108 @@
+use std::net::SocketAddr;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+const EVENTS_CAPACITY: usize = 1024;
+
+// Progress notifications published by the actors while a torrent is downloaded. They are
+// fanned out to every subscriber, e.g. the clients connected to the events stream.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TorrentEvent {
+    pub info_hash: String,
+    #[serde(flatten)]
+    pub kind: TorrentEventKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TorrentEventKind {
+    MetadataFetched {
+        name: String,
+    },
+    PeerFound {
+        endpoint: SocketAddr,
+    },
+    PieceDownloaded {
+        piece_idx: usize,
+        endpoint: SocketAddr,
+    },
+    PieceFailed {
+        piece_idx: usize,
+        endpoint: SocketAddr,
+    },
+    PieceWritten {
+        piece_idx: usize,
+    },
+    TorrentCompleted,
+    TorrentPaused,
+    TorrentResumed,
+    TorrentRemoved,
+}
+
+#[derive(Clone, Debug)]
+pub struct EventBus(broadcast::Sender<TorrentEvent>);
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        let (sender, _) = broadcast::channel(EVENTS_CAPACITY);
+        EventBus(sender)
+    }
+
+    // Events published while nobody is subscribed are simply dropped.
+    pub fn publish(&self, info_hash: String, kind: TorrentEventKind) {
+        let _ = self.0.send(TorrentEvent { info_hash, kind });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TorrentEvent> {
+        self.0.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn publish_to_every_subscriber() {
+        let bus = EventBus::new();
+        let mut first = bus.subscribe();
+        let mut second = bus.subscribe();
+
+        bus.publish("aa".to_owned(), TorrentEventKind::TorrentCompleted);
+
+        let expected = TorrentEvent {
+            info_hash: "aa".to_owned(),
+            kind: TorrentEventKind::TorrentCompleted,
+        };
+        assert_eq!(first.try_recv().unwrap(), expected);
+        assert_eq!(second.try_recv().unwrap(), expected);
+    }
+
+    #[test]
+    fn publish_without_subscribers() {
+        let bus = EventBus::new();
+        bus.publish("aa".to_owned(), TorrentEventKind::TorrentCompleted);
+
+        assert!(bus.subscribe().try_recv().is_err());
+    }
+
+    #[test]
+    fn serialize_event() {
+        let event = TorrentEvent {
+            info_hash: "aa".to_owned(),
+            kind: TorrentEventKind::PieceWritten { piece_idx: 3 },
+        };
+
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"info_hash":"aa","type":"piece_written","piece_idx":3}"#
+        );
+    }
+}
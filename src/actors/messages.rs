@@ -1,11 +1,15 @@
+use std::net::SocketAddr;
+
 use actix::prelude::*;
+use url::Url;
 
 use crate::{
-    torrent::{file::File, info::Info},
-    tracker::peer_endpoint::PeerEndpoint,
+    torrent::{file::File, info::Info, metafile::Metafile},
+    tracker::{announce::AnnounceEvent, peer_endpoint::PeerEndpoint, scrape::ScrapeStats},
 };
 
-use super::torrent::TorrentActor;
+use super::events::TorrentEventKind;
+use super::torrent::{AnnounceStats, TorrentActor, TorrentStatus};
 
 // EVENTS
 
@@ -14,6 +18,9 @@ use super::torrent::TorrentActor;
 pub struct TorrentRegistered {
     pub info_hash: Vec<u8>,
     pub torrent_actor_addr: Addr<TorrentActor>,
+    pub tracker_tiers: Vec<Vec<Url>>,
+    // Private torrents (BEP 27) are announced only to their own trackers.
+    pub private: bool,
 }
 
 #[derive(Message)]
@@ -25,7 +32,7 @@ pub struct PeerFound {
 #[derive(Message)]
 #[rtype(result = "Result<bool, std::io::Error>")]
 pub struct PieceDownloadSuccessfull {
-    pub endpoint: String,
+    pub endpoint: SocketAddr,
     pub piece: Vec<u8>,
     pub piece_idx: usize,
 }
@@ -33,7 +40,13 @@ pub struct PieceDownloadSuccessfull {
 #[derive(Message)]
 #[rtype(result = "Result<bool, std::io::Error>")]
 pub struct PieceDownloadFailed {
-    pub endpoint: String,
+    pub endpoint: SocketAddr,
+    pub piece_idx: usize,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct PieceWritten {
     pub piece_idx: usize,
 }
 
@@ -47,13 +60,105 @@ pub struct PieceReady {
     pub torrent_actor: Addr<TorrentActor>,
 }
 
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct SwarmScraped {
+    pub tracker: Url,
+    pub stats: ScrapeStats,
+}
+
+// A torrent completed, paused, resumed or removed, told straight to the trackers interface
+// so that it is announced. Events of the bus may be dropped when subscribers lag behind.
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct TorrentStateChanged {
+    pub info_hash: Vec<u8>,
+    pub kind: TorrentEventKind,
+}
+
+// The tracker of the torrent that answered the last announce.
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct TrackerAnswered {
+    pub info_hash: Vec<u8>,
+    pub tracker: Url,
+}
+
 // COMMANDS
 
 #[derive(Message)]
 #[rtype(result = "Result<bool, std::io::Error>")]
 pub struct PieceRequested {
-    pub endpoint: String,
+    pub endpoint: SocketAddr,
     pub info: Info,
     pub piece_idx: usize,
     pub torrent_actor: Addr<TorrentActor>,
 }
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct FetchMetadata;
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct RegisterTorrent {
+    pub info_hash: Vec<u8>,
+    pub torrent_actor_addr: Addr<TorrentActor>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct UnregisterTorrent {
+    pub info_hash: Vec<u8>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct PauseTorrent;
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct ResumeTorrent;
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct RemoveTorrent {
+    pub delete_data: bool,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct RemoveFiles {
+    pub files: Vec<File>,
+}
+
+// Announce a torrent to its trackers now, instead of waiting for the next interval.
+#[derive(Message)]
+#[rtype(result = "Result<bool, std::io::Error>")]
+pub struct Announce {
+    pub event: AnnounceEvent,
+}
+
+// QUERIES
+
+#[derive(Message)]
+#[rtype(result = "Vec<Addr<TorrentActor>>")]
+pub struct GetTorrents;
+
+#[derive(Message)]
+#[rtype(result = "Option<Addr<TorrentActor>>")]
+pub struct GetTorrent {
+    pub info_hash: Vec<u8>,
+}
+
+#[derive(Message)]
+#[rtype(result = "TorrentStatus")]
+pub struct GetTorrentStatus;
+
+#[derive(Message)]
+#[rtype(result = "Option<Metafile>")]
+pub struct GetTorrentFile;
+
+#[derive(Message)]
+#[rtype(result = "AnnounceStats")]
+pub struct GetAnnounceStats;
@@ -1,6 +1,8 @@
 pub mod connection;
+pub mod events;
 pub mod messages;
 pub mod torrent;
+pub mod torrents_registry;
 pub mod tracker;
 pub mod trackers_interface;
 pub mod writer;
@@ -1,43 +1,328 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use actix::prelude::*;
+use log::{debug, info, warn};
+use serde::Serialize;
+use url::Url;
 
 use crate::{
     actors::messages::PieceReady,
-    peer::{manager::get_info, piece_pool::PiecePool},
-    torrent::info::Info,
+    common::hex,
+    config::Config,
+    peer::{
+        manager::{get_info, PeerManagerError},
+        piece_pool::PiecePool,
+    },
+    torrent::{info::Info, magnet::Magnet, metafile::Metafile},
+    tracker::scrape::ScrapeStats,
 };
 
 use super::{
     connection::ConnectionActor,
-    messages::{PeerFound, PieceDownloadFailed, PieceDownloadSuccessfull, PieceRequested},
+    events::{EventBus, TorrentEventKind},
+    messages::{
+        FetchMetadata, GetAnnounceStats, GetTorrentFile, GetTorrentStatus, PauseTorrent, PeerFound,
+        PieceDownloadFailed, PieceDownloadSuccessfull, PieceRequested, PieceWritten, RemoveFiles,
+        RemoveTorrent, ResumeTorrent, SwarmScraped, TorrentStateChanged,
+    },
     writer::WriterActor,
 };
 
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
+// A peer is asked for the metadata this many times before it is given up.
+const MAX_METADATA_ATTEMPTS: usize = 3;
+const METADATA_RETRY_DELAY: Duration = Duration::from_secs(5);
+// Pieces are requested once this many peers are known, so that the first requests are spread
+// among them.
+const MIN_PEERS: usize = 11;
+// Left announced while the length of the torrent is unknown, like other clients do. Any
+// value but zero tells the tracker that the torrent is not complete.
+const UNKNOWN_LEFT: u64 = 16 * 1024;
+
 pub struct TorrentActor {
     connections_pool: Addr<ConnectionActor>,
+    connections: usize,
+    // Piece requests sent to the connections pool and not answered yet, each one continues
+    // a chain of requests.
+    pending_requests: usize,
     pub info: Option<Info>,
     pub info_hash: Vec<u8>,
     peers: Vec<Peer>,
     piece_available_pool: Option<PiecePool>,
     writers_pool: Addr<WriterActor>,
     initiated: bool,
+    paused: bool,
+    pieces_completed: usize,
+    bytes_completed: u64,
+    downloaded: u64,
+    events: EventBus,
+    metadata_dir: PathBuf,
+    display_name: Option<String>,
+    exact_length: Option<usize>,
+    banned_peers: Vec<SocketAddr>,
+    metadata_failures: HashMap<SocketAddr, usize>,
+    metadata_retry_scheduled: bool,
+    metadata_failed: bool,
+    min_peers: usize,
+    tracker_tiers: Vec<Vec<Url>>,
+    swarm: BTreeMap<String, ScrapeStats>,
+    trackers_interface: Option<Recipient<TorrentStateChanged>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TorrentState {
+    FetchingMetadata,
+    // Every peer tried failed to give the metadata.
+    MetadataFailed,
+    Downloading,
+    Paused,
+    Completed,
+}
+
+// Counters sent to the trackers in every announce, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnnounceStats {
+    pub uploaded: u64,
+    pub downloaded: u64,
+    pub left: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TorrentStatus {
+    pub info_hash: String,
+    pub name: Option<String>,
+    pub total_length: Option<usize>,
+    pub piece_count: Option<usize>,
+    pub pieces_completed: usize,
+    pub pieces_in_pool: usize,
+    pub peers: Vec<Peer>,
+    pub state: TorrentState,
+    // Last scrape of every tracker that knows the torrent, by tracker url.
+    pub swarm: BTreeMap<String, ScrapeStats>,
 }
 
 impl TorrentActor {
-    pub fn new(info_hash: Vec<u8>) -> TorrentActor {
-        let addr = SyncArbiter::start(5, || ConnectionActor);
-        let write_addr = SyncArbiter::start(1, || WriterActor);
+    pub fn new(info_hash: Vec<u8>, config: &Config, events: EventBus) -> TorrentActor {
+        let output_dir = config.storage.output_dir.to_owned();
+        let addr = SyncArbiter::start(config.peers.connections, || ConnectionActor);
+        let write_addr = SyncArbiter::start(1, move || WriterActor::new(output_dir.clone()));
 
         TorrentActor {
             connections_pool: addr,
+            connections: config.peers.connections,
+            pending_requests: 0,
             info: None,
             info_hash,
             peers: vec![],
             piece_available_pool: None,
             writers_pool: write_addr,
             initiated: false,
+            paused: false,
+            pieces_completed: 0,
+            bytes_completed: 0,
+            downloaded: 0,
+            events,
+            metadata_dir: config.storage.metadata_dir.to_owned(),
+            display_name: None,
+            exact_length: None,
+            banned_peers: vec![],
+            metadata_failures: HashMap::new(),
+            metadata_retry_scheduled: false,
+            metadata_failed: false,
+            min_peers: MIN_PEERS,
+            tracker_tiers: vec![],
+            swarm: BTreeMap::new(),
+            trackers_interface: None,
+        }
+    }
+
+    // The name and length given by a magnet are shown until the real info is fetched. Web
+    // seeds are only logged, as pieces are downloaded from peers alone.
+    pub fn with_magnet(magnet: &Magnet, config: &Config, events: EventBus) -> TorrentActor {
+        let mut actor = TorrentActor::new(magnet.get_info_hash(), config, events);
+        let web_seeds = magnet.get_web_seeds();
+        if !web_seeds.is_empty() {
+            info!("Ignoring web seeds {:?}, not supported yet", web_seeds);
+        }
+        actor.display_name = magnet.get_display_name();
+        actor.exact_length = magnet.get_exact_length();
+        actor.tracker_tiers = magnet.get_tracker_tiers();
+        actor
+    }
+
+    // Used when the info is already known from a .torrent file, so that no metadata has to
+    // be fetched from the peers.
+    pub fn with_metafile(metafile: &Metafile, config: &Config, events: EventBus) -> TorrentActor {
+        let mut actor = TorrentActor::new(metafile.get_info_hash(), config, events);
+        actor.set_info(metafile.get_info().clone());
+        actor.tracker_tiers = metafile.get_tracker_tiers();
+        actor
+    }
+
+    // The trackers interface announces when the torrent completes, pauses, resumes or is
+    // removed.
+    pub fn trackers_interface(mut self, recipient: Recipient<TorrentStateChanged>) -> Self {
+        self.trackers_interface = Some(recipient);
+        self
+    }
+
+    pub fn min_peers(mut self, min_peers: usize) -> Self {
+        self.min_peers = min_peers;
+        self
+    }
+
+    fn set_info(&mut self, info: Info) {
+        self.piece_available_pool = Some(PiecePool::new(info.get_piece_count()));
+        self.info = Some(info);
+    }
+
+    // Ask the connections pool for the next available piece. Nothing is requested while the
+    // torrent is paused, so the chain of requests started by each connection stops.
+    fn request_next_piece(&mut self, endpoint: SocketAddr, ctx: &mut Context<Self>) {
+        if self.paused {
+            return;
+        }
+
+        if let Some(piece_idx) = self.piece_available_pool.as_ref().unwrap().pop() {
+            let msg = PieceRequested {
+                piece_idx,
+                info: self.info.as_ref().unwrap().clone(),
+                endpoint,
+                torrent_actor: ctx.address(),
+            };
+            self.connections_pool.do_send(msg);
+            self.pending_requests += 1;
+        }
+    }
+
+    // Downloading starts once the info is known and enough peers have been found.
+    fn start_download(&mut self, ctx: &mut Context<Self>) {
+        if self.info.is_some()
+            && self.peers.len() >= self.min_peers
+            && !self.initiated
+            && !self.paused
+        {
+            self.initiated = true;
+            self.request_pieces(ctx);
+        }
+    }
+
+    // Chains still running, e.g. when resuming right after a pause, are not started again.
+    fn request_pieces(&mut self, ctx: &mut Context<Self>) {
+        for _ in self.pending_requests..self.connections {
+            let endpoint = Peer::find_suitable_peer(self.peers.to_vec());
+            self.request_next_piece(endpoint, ctx);
+        }
+    }
+
+    // Peers sending metadata that does not match the info hash are dropped and never used
+    // again, even when the trackers report them once more.
+    fn fetch_info(&mut self, endpoint: SocketAddr) -> Result<(), PeerManagerError> {
+        match collect_info(&self.info_hash, endpoint, &self.metadata_dir) {
+            Ok(info) => {
+                self.publish(TorrentEventKind::MetadataFetched {
+                    name: info.get_name(),
+                });
+                self.set_info(info);
+                Ok(())
+            }
+            Err(PeerManagerError::MetadataVerificationFailure()) => {
+                warn!(
+                    "Peer {:?} sent metadata not matching info hash {:?}, banned",
+                    endpoint,
+                    hex::encode(&self.info_hash)
+                );
+                self.peers.retain(|peer| peer.endpoint != endpoint);
+                self.banned_peers.push(endpoint);
+                Err(PeerManagerError::MetadataVerificationFailure())
+            }
+            Err(err) => {
+                debug!("Metadata not fetched from peer {:?}: {:?}", endpoint, err);
+                *self.metadata_failures.entry(endpoint).or_default() += 1;
+                Err(err)
+            }
+        }
+    }
+
+    // The known peer asked the fewest times for the metadata, if any can still be asked.
+    fn metadata_candidate(&self) -> Option<SocketAddr> {
+        self.peers
+            .iter()
+            .map(|peer| {
+                let failures = self.metadata_failures.get(&peer.endpoint).copied();
+                (peer.endpoint, failures.unwrap_or(0))
+            })
+            .filter(|(_, failures)| *failures < MAX_METADATA_ATTEMPTS)
+            .min_by_key(|(_, failures)| *failures)
+            .map(|(endpoint, _)| endpoint)
+    }
+
+    // Retries are spaced so that a peer failing for a moment is not given up right away, and a
+    // single retry is scheduled at a time however many peers fail.
+    fn retry_fetch_info(&mut self, ctx: &mut Context<Self>) {
+        if self.metadata_retry_scheduled {
+            return;
+        }
+
+        if self.metadata_candidate().is_some() {
+            self.metadata_retry_scheduled = true;
+            ctx.run_later(METADATA_RETRY_DELAY, |actor, ctx| {
+                actor.metadata_retry_scheduled = false;
+                ctx.notify(FetchMetadata);
+            });
+        } else if !self.metadata_failed {
+            warn!(
+                "Metadata of {:?} not fetched from any of the {} known peers",
+                hex::encode(&self.info_hash),
+                self.peers.len()
+            );
+            self.metadata_failed = true;
+        }
+    }
+
+    fn publish(&self, kind: TorrentEventKind) {
+        self.events.publish(hex::encode(&self.info_hash), kind);
+    }
+
+    // Changes to announce are sent to the trackers interface as well as published.
+    fn change_state(&self, kind: TorrentEventKind) {
+        if let Some(trackers_interface) = &self.trackers_interface {
+            trackers_interface.do_send(TorrentStateChanged {
+                info_hash: self.info_hash.clone(),
+                kind: kind.clone(),
+            });
+        }
+        self.publish(kind);
+    }
+
+    fn is_completed(&self) -> bool {
+        self.info
+            .as_ref()
+            .is_some_and(|info| self.pieces_completed >= info.get_piece_count())
+    }
+
+    fn left(&self) -> u64 {
+        match &self.info {
+            Some(info) => (info.get_total_length() as u64).saturating_sub(self.bytes_completed),
+            None => self
+                .exact_length
+                .map_or(UNKNOWN_LEFT, |length| length as u64),
+        }
+    }
+
+    fn state(&self) -> TorrentState {
+        match &self.info {
+            _ if self.is_completed() => TorrentState::Completed,
+            _ if self.paused => TorrentState::Paused,
+            None if self.metadata_failed => TorrentState::MetadataFailed,
+            None => TorrentState::FetchingMetadata,
+            Some(_) => TorrentState::Downloading,
         }
     }
 }
@@ -55,6 +340,8 @@ impl Handler<PieceDownloadSuccessfull> for TorrentActor {
     type Result = Result<bool, std::io::Error>;
 
     fn handle(&mut self, msg: PieceDownloadSuccessfull, ctx: &mut Context<Self>) -> Self::Result {
+        self.pending_requests = self.pending_requests.saturating_sub(1);
+        self.downloaded += msg.piece.len() as u64;
         let msg_ready = PieceReady {
             piece: msg.piece,
             files: self.info.as_ref().unwrap().get_files().unwrap(),
@@ -64,18 +351,15 @@ impl Handler<PieceDownloadSuccessfull> for TorrentActor {
         };
         self.writers_pool.do_send(msg_ready);
 
+        self.publish(TorrentEventKind::PieceDownloaded {
+            piece_idx: msg.piece_idx,
+            endpoint: msg.endpoint,
+        });
+
         let endpoint = msg.endpoint;
-        Peer::update_sucess(&mut self.peers, endpoint.clone());
+        Peer::update_sucess(&mut self.peers, endpoint);
 
-        if let Some(piece_idx) = self.piece_available_pool.as_mut().unwrap().pop() {
-            let msg = PieceRequested {
-                piece_idx,
-                info: self.info.as_ref().unwrap().clone(),
-                endpoint,
-                torrent_actor: ctx.address(),
-            };
-            self.connections_pool.do_send(msg);
-        }
+        self.request_next_piece(endpoint, ctx);
 
         Ok(true)
     }
@@ -85,100 +369,265 @@ impl Handler<PieceDownloadFailed> for TorrentActor {
     type Result = Result<bool, std::io::Error>;
 
     fn handle(&mut self, msg: PieceDownloadFailed, ctx: &mut Context<Self>) -> Self::Result {
+        self.pending_requests = self.pending_requests.saturating_sub(1);
         self.piece_available_pool
             .as_ref()
             .unwrap()
             .insert(msg.piece_idx);
 
-        let endpoint = msg.endpoint.as_str();
-        Peer::update_failed(&mut self.peers, endpoint.to_string());
+        self.publish(TorrentEventKind::PieceFailed {
+            piece_idx: msg.piece_idx,
+            endpoint: msg.endpoint,
+        });
+
+        Peer::update_failed(&mut self.peers, msg.endpoint);
 
         let endpoint = Peer::find_suitable_peer(self.peers.to_vec());
+        self.request_next_piece(endpoint, ctx);
 
-        if let Some(piece_idx) = self.piece_available_pool.as_mut().unwrap().pop() {
-            let msg = PieceRequested {
-                piece_idx,
-                info: self.info.as_ref().unwrap().clone(),
-                endpoint,
-                torrent_actor: ctx.address(),
-            };
-            self.connections_pool.do_send(msg);
+        Ok(true)
+    }
+}
+
+impl Handler<PieceWritten> for TorrentActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, msg: PieceWritten, _ctx: &mut Context<Self>) -> Self::Result {
+        self.pieces_completed += 1;
+        if let Some(info) = &self.info {
+            self.bytes_completed += info.get_piece_size(msg.piece_idx) as u64;
+        }
+        debug!(
+            "Piece {:?} written, {:?} pieces completed",
+            msg.piece_idx, self.pieces_completed
+        );
+
+        self.publish(TorrentEventKind::PieceWritten {
+            piece_idx: msg.piece_idx,
+        });
+
+        if self.is_completed() {
+            info!("Torrent {:?} completed", hex::encode(&self.info_hash));
+            self.change_state(TorrentEventKind::TorrentCompleted);
+        }
+
+        Ok(true)
+    }
+}
+
+impl Handler<PauseTorrent> for TorrentActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, _msg: PauseTorrent, _ctx: &mut Context<Self>) -> Self::Result {
+        if !self.paused {
+            self.change_state(TorrentEventKind::TorrentPaused);
+        }
+        self.paused = true;
+
+        Ok(true)
+    }
+}
+
+impl Handler<ResumeTorrent> for TorrentActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, _msg: ResumeTorrent, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.paused {
+            return Ok(false);
+        }
+        self.paused = false;
+        self.change_state(TorrentEventKind::TorrentResumed);
+
+        if self.initiated && !self.peers.is_empty() {
+            self.request_pieces(ctx);
         }
 
         Ok(true)
     }
 }
 
+impl Handler<RemoveTorrent> for TorrentActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, msg: RemoveTorrent, ctx: &mut Context<Self>) -> Self::Result {
+        self.paused = true;
+
+        if msg.delete_data {
+            // The writers pool handles the messages in order, so the files are removed only
+            // after every piece already queued has been written.
+            if let Some(files) = self.info.as_ref().and_then(|info| info.get_files().ok()) {
+                self.writers_pool.do_send(RemoveFiles { files });
+            }
+
+            let _ = std::fs::remove_file(info_cache_path(&self.metadata_dir, &self.info_hash));
+        }
+
+        self.change_state(TorrentEventKind::TorrentRemoved);
+        ctx.stop();
+
+        Ok(true)
+    }
+}
+
+impl Handler<GetTorrentStatus> for TorrentActor {
+    type Result = MessageResult<GetTorrentStatus>;
+
+    fn handle(&mut self, _msg: GetTorrentStatus, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(TorrentStatus {
+            info_hash: hex::encode(&self.info_hash),
+            name: self
+                .info
+                .as_ref()
+                .map(|info| info.get_name())
+                .or(self.display_name.clone()),
+            total_length: self
+                .info
+                .as_ref()
+                .map(|info| info.get_total_length())
+                .or(self.exact_length),
+            piece_count: self.info.as_ref().map(|info| info.get_piece_count()),
+            pieces_completed: self.pieces_completed,
+            pieces_in_pool: self
+                .piece_available_pool
+                .as_ref()
+                .map_or(0, |pool| pool.len()),
+            peers: self.peers.to_vec(),
+            state: self.state(),
+            swarm: self.swarm.clone(),
+        })
+    }
+}
+
+impl Handler<SwarmScraped> for TorrentActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, msg: SwarmScraped, _ctx: &mut Context<Self>) -> Self::Result {
+        self.swarm.insert(msg.tracker.to_string(), msg.stats);
+
+        Ok(true)
+    }
+}
+
+impl Handler<GetAnnounceStats> for TorrentActor {
+    type Result = MessageResult<GetAnnounceStats>;
+
+    // Pieces are never uploaded to other peers, only downloaded.
+    fn handle(&mut self, _msg: GetAnnounceStats, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(AnnounceStats {
+            uploaded: 0,
+            downloaded: self.downloaded,
+            left: self.left(),
+        })
+    }
+}
+
+// The .torrent is available once the info is known, with the trackers of the torrent.
+impl Handler<GetTorrentFile> for TorrentActor {
+    type Result = Option<Metafile>;
+
+    fn handle(&mut self, _msg: GetTorrentFile, _ctx: &mut Context<Self>) -> Self::Result {
+        let announce_list: Vec<Vec<String>> = self
+            .tracker_tiers
+            .iter()
+            .map(|tier| tier.iter().map(|url| url.to_string()).collect())
+            .collect();
+
+        self.info
+            .as_ref()
+            .map(|info| Metafile::new(info.clone(), announce_list, None, None, None))
+    }
+}
+
 impl Handler<PeerFound> for TorrentActor {
     type Result = Result<bool, std::io::Error>;
 
     fn handle(&mut self, msg: PeerFound, ctx: &mut Context<Self>) -> Self::Result {
-        self.peers.push(Peer::new(msg.peer.endpoint()));
+        let endpoint = msg.peer.endpoint();
+        // Trackers and magnets often list the same peers again.
+        if self.banned_peers.contains(&endpoint)
+            || self.peers.iter().any(|peer| peer.endpoint == endpoint)
+        {
+            return Ok(false);
+        }
 
-        match &self.info {
-            None => {
-                if let Some(info) = collect_info(&self.info_hash, &msg.peer.endpoint()) {
-                    let piece_count = (0..info.get_total_length())
-                        .step_by(info.get_piece_length())
-                        .len();
+        self.peers.push(Peer::new(endpoint));
+        self.publish(TorrentEventKind::PeerFound { endpoint });
 
-                    self.piece_available_pool = Some(PiecePool::new(piece_count));
-                    self.info = Some(info);
-                }
+        if self.info.is_some() {
+            self.start_download(ctx);
+            return Ok(true);
+        }
 
-                return Ok(true);
-            }
-            Some(info) => {
-                if self.peers.len() > 10 && !self.initiated {
-                    for _ in 0..5 {
-                        let endpoint = Peer::find_suitable_peer(self.peers.to_vec());
-
-                        if let Some(piece_idx) = self.piece_available_pool.as_mut().unwrap().pop() {
-                            let msg = PieceRequested {
-                                piece_idx,
-                                info: info.clone(),
-                                endpoint,
-                                torrent_actor: ctx.address(),
-                            };
-
-                            self.connections_pool.do_send(msg);
-                            self.initiated = true;
-                        }
-                    }
-                }
-            }
+        // A new peer is another chance to get the metadata.
+        self.metadata_failed = false;
+        match self.fetch_info(endpoint) {
+            Ok(()) => self.start_download(ctx),
+            Err(_) => self.retry_fetch_info(ctx),
         }
 
         Ok(true)
     }
 }
 
-fn collect_info(info_hash: &[u8], endpoint: &str) -> Option<Info> {
-    let filename = urlencoding::encode_binary(info_hash).into_owned();
-    let file_path = format!("./downloads/{filename}");
-    std::fs::create_dir_all("./downloads").unwrap();
+// Retry the metadata fetch from one of the known peers, after a peer failed to give it.
+impl Handler<FetchMetadata> for TorrentActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, _msg: FetchMetadata, ctx: &mut Context<Self>) -> Self::Result {
+        if self.info.is_some() {
+            return Ok(false);
+        }
 
-    if let Ok(info) = Info::from_file(&file_path) {
-        return Some(info);
+        let Some(endpoint) = self.metadata_candidate() else {
+            self.retry_fetch_info(ctx);
+            return Ok(false);
+        };
+        match self.fetch_info(endpoint) {
+            Ok(()) => self.start_download(ctx),
+            Err(_) => self.retry_fetch_info(ctx),
+        }
+
+        Ok(true)
     }
+}
+
+fn info_cache_path(metadata_dir: &Path, info_hash: &[u8]) -> PathBuf {
+    metadata_dir.join(format!("{}.torrent", hex::encode(info_hash)))
+}
 
-    if let Ok(info) = get_info(info_hash, endpoint) {
-        let _ = info.save(&file_path);
-        return Some(info);
-    };
+// A cached info is used only when it matches the info hash, otherwise it is discarded and
+// fetched again from the peer.
+fn collect_info(
+    info_hash: &[u8],
+    endpoint: SocketAddr,
+    metadata_dir: &Path,
+) -> Result<Info, PeerManagerError> {
+    let file_path = info_cache_path(metadata_dir, info_hash);
+    let _ = std::fs::create_dir_all(metadata_dir);
+
+    match Info::from_file(&file_path) {
+        Ok(info) if info.compute_info_hash() == info_hash => return Ok(info),
+        Ok(_) => {
+            warn!("Cached info {:?} does not match the info hash", file_path);
+            let _ = std::fs::remove_file(&file_path);
+        }
+        Err(_) => (),
+    }
 
-    None
+    let info = get_info(info_hash, endpoint)?;
+    let _ = info.save(&file_path);
+    Ok(info)
 }
 
-#[derive(Clone, Debug)]
-struct Peer {
-    endpoint: String,
+#[derive(Clone, Debug, Serialize)]
+pub struct Peer {
+    endpoint: SocketAddr,
     piece_downloaded: usize,
     piece_failed: usize,
 }
 
 impl Peer {
-    fn new(endpoint: String) -> Peer {
+    fn new(endpoint: SocketAddr) -> Peer {
         Peer {
             endpoint,
             piece_downloaded: 0,
@@ -186,7 +635,7 @@ impl Peer {
         }
     }
 
-    fn update_failed(pool: &mut Vec<Peer>, endpoint: String) {
+    fn update_failed(pool: &mut Vec<Peer>, endpoint: SocketAddr) {
         for peer in pool {
             if peer.endpoint == endpoint {
                 peer.piece_failed += 1;
@@ -194,7 +643,7 @@ impl Peer {
         }
     }
 
-    fn update_sucess(pool: &mut Vec<Peer>, endpoint: String) {
+    fn update_sucess(pool: &mut Vec<Peer>, endpoint: SocketAddr) {
         for peer in pool {
             if peer.endpoint == endpoint {
                 peer.piece_downloaded += 1;
@@ -202,21 +651,62 @@ impl Peer {
         }
     }
 
-    fn find_suitable_peer(mut pool: Vec<Peer>) -> String {
+    fn find_suitable_peer(mut pool: Vec<Peer>) -> SocketAddr {
         pool.shuffle(&mut thread_rng());
 
         for peer in &pool {
             if peer.piece_failed < 2 {
-                return peer.endpoint.to_owned();
+                return peer.endpoint;
             }
         }
 
         for peer in &pool {
             if peer.piece_failed < 4 {
-                return peer.endpoint.to_owned();
+                return peer.endpoint;
             }
         }
 
-        return pool.first().unwrap().endpoint.to_owned();
+        pool.first().unwrap().endpoint
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tracker::peer_endpoint::PeerEndpoint;
+
+    #[actix_web::test]
+    async fn known_peers_are_not_added_again() {
+        let info = Info::new("a".to_owned(), 4, vec![0; 20], None, Some(3), false);
+        let metafile = Metafile::new(info, vec![], None, None, None);
+        let torrent =
+            TorrentActor::with_metafile(&metafile, &Config::default(), EventBus::new()).start();
+        let peer = PeerEndpoint::from("10.0.0.1:6881".parse::<SocketAddr>().unwrap());
+
+        let first = torrent.send(PeerFound { peer }).await.unwrap();
+        let second = torrent.send(PeerFound { peer }).await.unwrap();
+        let status = torrent.send(GetTorrentStatus).await.unwrap();
+
+        assert!(first.unwrap());
+        assert!(!second.unwrap());
+        assert_eq!(status.peers.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn resume_does_not_restart_running_requests() {
+        let info = Info::new("a".to_owned(), 4, vec![0; 400], None, Some(80), false);
+        let metafile = Metafile::new(info, vec![], None, None, None);
+        let torrent = TorrentActor::with_metafile(&metafile, &Config::default(), EventBus::new())
+            .min_peers(1)
+            .start();
+        // Nothing answers at this address, the requests stay running while the test lasts.
+        let peer = PeerEndpoint::from("192.0.2.1:6881".parse::<SocketAddr>().unwrap());
+
+        torrent.send(PeerFound { peer }).await.unwrap().unwrap();
+        torrent.send(PauseTorrent).await.unwrap().unwrap();
+        torrent.send(ResumeTorrent).await.unwrap().unwrap();
+        let status = torrent.send(GetTorrentStatus).await.unwrap();
+
+        assert_eq!(status.pieces_in_pool, 15);
     }
 }
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use actix::prelude::*;
+
+use super::{
+    messages::{GetTorrent, GetTorrents, RegisterTorrent, UnregisterTorrent},
+    torrent::TorrentActor,
+};
+
+// Keeps track of every torrent started by the client so that they can be queried later on.
+pub struct TorrentsRegistryActor {
+    torrents: HashMap<Vec<u8>, Addr<TorrentActor>>,
+}
+
+impl TorrentsRegistryActor {
+    pub fn new() -> TorrentsRegistryActor {
+        TorrentsRegistryActor {
+            torrents: HashMap::new(),
+        }
+    }
+}
+
+// Provide Actor implementation for our actor
+impl Actor for TorrentsRegistryActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _ctx: &mut Context<Self>) {}
+
+    fn stopped(&mut self, _ctx: &mut Context<Self>) {}
+}
+
+impl Handler<RegisterTorrent> for TorrentsRegistryActor {
+    type Result = Result<bool, std::io::Error>;
+
+    // A torrent already registered is left untouched, so that the same info hash is never
+    // downloaded twice at the same time.
+    fn handle(&mut self, msg: RegisterTorrent, _ctx: &mut Context<Self>) -> Self::Result {
+        if self.torrents.contains_key(&msg.info_hash) {
+            return Ok(false);
+        }
+        self.torrents.insert(msg.info_hash, msg.torrent_actor_addr);
+
+        Ok(true)
+    }
+}
+
+impl Handler<UnregisterTorrent> for TorrentsRegistryActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, msg: UnregisterTorrent, _ctx: &mut Context<Self>) -> Self::Result {
+        Ok(self.torrents.remove(&msg.info_hash).is_some())
+    }
+}
+
+impl Handler<GetTorrents> for TorrentsRegistryActor {
+    type Result = Vec<Addr<TorrentActor>>;
+
+    fn handle(&mut self, _msg: GetTorrents, _ctx: &mut Context<Self>) -> Self::Result {
+        self.torrents.values().cloned().collect()
+    }
+}
+
+impl Handler<GetTorrent> for TorrentsRegistryActor {
+    type Result = Option<Addr<TorrentActor>>;
+
+    fn handle(&mut self, msg: GetTorrent, _ctx: &mut Context<Self>) -> Self::Result {
+        self.torrents.get(&msg.info_hash).cloned()
+    }
+}
@@ -1,46 +1,442 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use actix::prelude::*;
+use log::{debug, error};
 use url::Url;
 
 use crate::{
-    actors::messages::PeerFound,
-    tracker::{self, peer_endpoint::PeerEndpoint},
+    actors::messages::{Announce, GetAnnounceStats, PeerFound, TrackerAnswered},
+    actors::torrent::{AnnounceStats, TorrentActor},
+    common::hex,
+    tracker::{
+        self,
+        announce::{AnnounceEvent, AnnounceRequest, AnnounceResponse, DEFAULT_INTERVAL},
+        tcp_tracker::HttpClient,
+        tiers::TrackerTiers,
+        TrackerError,
+    },
 };
 
-use super::messages::TorrentRegistered;
+// A failed announce is tried again after this delay, or after the min interval of the
+// tracker when that is longer.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+// Announces never come closer than this, whatever interval the tracker asks for.
+const MIN_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(60);
+// The next tracker is tried when one does not answer in time, as a UDP tracker alone may
+// keep retransmitting for about two hours.
+const TRACKER_TIMEOUT: Duration = Duration::from_secs(60);
 
+// Announces a torrent to its trackers, again and again at the interval the tracker asks
+// for. Trackers are tried one after the other following their tiers (BEP 12), until one
+// of them answers.
 pub struct TrackerActor {
-    pub url: Url,
+    info_hash: Vec<u8>,
+    torrent: Addr<TorrentActor>,
+    answered: Recipient<TrackerAnswered>,
+    tiers: TrackerTiers,
+    port: u16,
+    peer_id: String,
+    key: u32,
+    http: HttpClient,
+    tracker_ids: HashMap<Url, String>,
+    interval: Duration,
+    min_interval: Option<Duration>,
+    interval_floor: Duration,
+    last_stats: AnnounceStats,
+    // Event to send again when the announce carrying it failed.
+    pending_event: AnnounceEvent,
+    stopped: bool,
+    next_announce: Option<SpawnHandle>,
+}
+
+impl TrackerActor {
+    pub fn new(
+        info_hash: Vec<u8>,
+        torrent: Addr<TorrentActor>,
+        answered: Recipient<TrackerAnswered>,
+        tiers: TrackerTiers,
+    ) -> TrackerActor {
+        TrackerActor {
+            info_hash,
+            torrent,
+            answered,
+            tiers,
+            port: 0,
+            peer_id: String::new(),
+            key: 0,
+            http: HttpClient::default(),
+            tracker_ids: HashMap::new(),
+            interval: DEFAULT_INTERVAL,
+            min_interval: None,
+            interval_floor: MIN_ANNOUNCE_INTERVAL,
+            last_stats: AnnounceStats::default(),
+            pending_event: AnnounceEvent::None,
+            stopped: false,
+            next_announce: None,
+        }
+    }
+
+    // Port, peer id and key are the same in the announces of every torrent.
+    pub fn peer(mut self, port: u16, peer_id: String, key: u32) -> Self {
+        self.port = port;
+        self.peer_id = peer_id;
+        self.key = key;
+        self
+    }
+
+    pub fn http_client(mut self, http: HttpClient) -> Self {
+        self.http = http;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn interval_floor(mut self, interval_floor: Duration) -> Self {
+        self.interval_floor = interval_floor;
+        self
+    }
+
+    // Regular announces follow the interval, but never come before the min interval.
+    fn next_interval(&self) -> Duration {
+        self.interval
+            .max(self.min_interval.unwrap_or_default())
+            .max(self.interval_floor)
+    }
+
+    fn retry_interval(&self) -> Duration {
+        RETRY_INTERVAL.max(self.min_interval.unwrap_or_default())
+    }
+
+    fn announce(&mut self, event: AnnounceEvent, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.next_announce.take() {
+            ctx.cancel_future(handle);
+        }
+
+        // The torrent actor is gone without a stopped event, e.g. the server shut down.
+        if !self.torrent.connected() && event != AnnounceEvent::Stopped {
+            ctx.stop();
+            return;
+        }
+
+        let event = match (event, self.pending_event) {
+            (AnnounceEvent::None, pending) => pending,
+            (event, _) => event,
+        };
+        self.stopped = event == AnnounceEvent::Stopped;
+
+        let torrent = self.torrent.clone();
+        let last_stats = self.last_stats;
+        let mut request = AnnounceRequest::new(&self.info_hash, &self.peer_id, self.port)
+            .key(self.key)
+            .compact(true)
+            .event(event);
+        // Peers are of no use once the torrent is stopped.
+        if event == AnnounceEvent::Stopped {
+            request = request.numwant(0);
+        }
+        let trackers: Vec<(Url, Option<String>)> = self
+            .tiers
+            .get_trackers()
+            .into_iter()
+            .map(|url| {
+                let tracker_id = self.tracker_ids.get(&url).cloned();
+                (url, tracker_id)
+            })
+            .collect();
+        let http = self.http.clone();
+
+        async move {
+            let stats = torrent.send(GetAnnounceStats).await.unwrap_or(last_stats);
+            let request = request
+                .uploaded(stats.uploaded)
+                .downloaded(stats.downloaded)
+                .left(stats.left);
+
+            let result = announce_to_first(&trackers, request, &http, TRACKER_TIMEOUT).await;
+            (stats, result)
+        }
+        .into_actor(self)
+        .map(move |(stats, result), actor, ctx| actor.announced(event, stats, result, ctx))
+        .spawn(ctx);
+    }
+
+    fn announced(
+        &mut self,
+        event: AnnounceEvent,
+        stats: AnnounceStats,
+        result: Result<(Url, AnnounceResponse), TrackerError>,
+        ctx: &mut Context<Self>,
+    ) {
+        self.last_stats = stats;
+
+        let delay = match result {
+            Ok((url, response)) => {
+                debug!(
+                    "Announced {:?} to {:?} with event {:?}, seeders {:?} leechers {:?}",
+                    hex::encode(&self.info_hash),
+                    url.as_str(),
+                    event,
+                    response.get_complete(),
+                    response.get_incomplete()
+                );
+                self.tiers.promote(&url);
+                self.pending_event = AnnounceEvent::None;
+                self.interval = response.get_interval();
+                self.min_interval = response.get_min_interval();
+                if let Some(tracker_id) = response.get_tracker_id() {
+                    self.tracker_ids.insert(url.clone(), tracker_id.to_owned());
+                }
+
+                if !self.stopped {
+                    for peer in response.get_peers() {
+                        self.torrent.do_send(PeerFound { peer: *peer });
+                    }
+                    self.answered.do_send(TrackerAnswered {
+                        info_hash: self.info_hash.clone(),
+                        tracker: url,
+                    });
+                }
+                self.next_interval()
+            }
+            Err(err) => {
+                error!(
+                    "Announce of {:?} failed on every tracker, last error: {}",
+                    hex::encode(&self.info_hash),
+                    err
+                );
+                if event != AnnounceEvent::Stopped {
+                    self.pending_event = event;
+                }
+                self.retry_interval()
+            }
+        };
+
+        if self.stopped {
+            // A removed torrent is forgotten, a paused one waits to be started again.
+            if !self.torrent.connected() {
+                ctx.stop();
+            }
+            return;
+        }
+
+        self.next_announce =
+            Some(ctx.run_later(delay, |actor, ctx| actor.announce(AnnounceEvent::None, ctx)));
+    }
+}
+
+// Trackers are tried in order, each one for at most the given time, the first one answering
+// is returned with its response.
+async fn announce_to_first(
+    trackers: &[(Url, Option<String>)],
+    request: AnnounceRequest,
+    http: &HttpClient,
+    timeout: Duration,
+) -> Result<(Url, AnnounceResponse), TrackerError> {
+    let mut last_error = TrackerError::NoTracker();
+
+    for (url, tracker_id) in trackers {
+        let request = request.clone().tracker_id(tracker_id.clone());
+        let announced = tokio::time::timeout(timeout, tracker::announce(url, &request, http))
+            .await
+            .unwrap_or(Err(TrackerError::Timeout(timeout)));
+        match announced {
+            Ok(response) => return Ok((url.clone(), response)),
+            Err(err) => {
+                debug!("Announce to {:?} failed: {}", url.as_str(), err);
+                last_error = err;
+            }
+        }
+    }
+
+    Err(last_error)
 }
 
 // Provide Actor implementation for our actor
 impl Actor for TrackerActor {
     type Context = Context<Self>;
 
-    fn started(&mut self, _ctx: &mut Context<Self>) {}
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        self.announce(AnnounceEvent::Started, ctx);
+    }
 
     fn stopped(&mut self, _ctx: &mut Context<Self>) {}
 }
 
-impl Handler<TorrentRegistered> for TrackerActor {
+impl Handler<Announce> for TrackerActor {
     type Result = Result<bool, std::io::Error>;
 
-    fn handle(&mut self, msg: TorrentRegistered, ctx: &mut Context<Self>) -> Self::Result {
-        let url = self.url.clone();
-
-        async move {
-            let result = tracker::get_peers_by_tracker(&url, &msg.info_hash).await;
-
-            if let Ok(peers) = result {
-                for peer in peers {
-                    if PeerEndpoint::is_reachable(&peer.endpoint()) {
-                        msg.torrent_actor_addr.do_send(PeerFound { peer });
-                    }
-                }
+    fn handle(&mut self, msg: Announce, ctx: &mut Context<Self>) -> Self::Result {
+        if self.stopped && msg.event != AnnounceEvent::Started {
+            if !self.torrent.connected() {
+                ctx.stop();
             }
+            return Ok(false);
         }
-        .into_actor(self)
-        .spawn(ctx);
+
+        self.announce(msg.event, ctx);
 
         Ok(true)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{actors::events::EventBus, config::Config};
+
+    use httpmock::prelude::*;
+
+    // Collects the trackers that answered, in place of the trackers interface.
+    struct Answers(Vec<Url>);
+
+    impl Actor for Answers {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<TrackerAnswered> for Answers {
+        type Result = Result<bool, std::io::Error>;
+
+        fn handle(&mut self, msg: TrackerAnswered, _ctx: &mut Context<Self>) -> Self::Result {
+            self.0.push(msg.tracker);
+            Ok(true)
+        }
+    }
+
+    fn start_tracker(tiers: Vec<Vec<Url>>, info_hash: &[u8]) -> Addr<TrackerActor> {
+        let torrent =
+            TorrentActor::new(info_hash.to_vec(), &Config::default(), EventBus::new()).start();
+        let answers = Answers(vec![]).start();
+
+        TrackerActor::new(
+            info_hash.to_vec(),
+            torrent,
+            answers.recipient(),
+            TrackerTiers::new(tiers),
+        )
+        .peer(6881, "peer_id".to_owned(), 1)
+        .interval_floor(Duration::from_secs(1))
+        .start()
+    }
+
+    fn announce_url(server: &MockServer) -> Url {
+        Url::parse(&server.url("/announce")).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn announce_again_after_interval() {
+        let server = MockServer::start();
+        let announces = server.mock(|when, then| {
+            when.method(GET)
+                .path("/announce")
+                .query_param("left", "16384");
+            then.status(200).body("d8:intervali1e5:peers0:e");
+        });
+
+        let _tracker = start_tracker(vec![vec![announce_url(&server)]], &[0x01]);
+        actix_web::rt::time::sleep(Duration::from_millis(1500)).await;
+
+        assert_eq!(announces.hits(), 2);
+    }
+
+    #[actix_web::test]
+    async fn interval_is_never_below_the_floor() {
+        let server = MockServer::start();
+        let announces = server.mock(|when, then| {
+            when.method(GET).path("/announce");
+            then.status(200)
+                .body("d8:intervali0e12:min intervali0e5:peers0:e");
+        });
+
+        let _tracker = start_tracker(vec![vec![announce_url(&server)]], &[0x04]);
+        actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+
+        assert_eq!(announces.hits(), 1);
+    }
+
+    #[actix_web::test]
+    async fn stopped_torrent_is_not_announced_again() {
+        let server = MockServer::start();
+        let started = server.mock(|when, then| {
+            when.method(GET)
+                .path("/announce")
+                .query_param("event", "started");
+            then.status(200).body("d8:intervali1e5:peers0:e");
+        });
+        let stopped = server.mock(|when, then| {
+            when.method(GET)
+                .path("/announce")
+                .query_param("event", "stopped")
+                .query_param("numwant", "0");
+            then.status(200).body("d8:intervali1e5:peers0:e");
+        });
+
+        let tracker = start_tracker(vec![vec![announce_url(&server)]], &[0x02]);
+        actix_web::rt::time::sleep(Duration::from_millis(200)).await;
+        let announced = tracker
+            .send(Announce {
+                event: AnnounceEvent::Stopped,
+            })
+            .await
+            .unwrap();
+        actix_web::rt::time::sleep(Duration::from_millis(1500)).await;
+
+        assert!(announced.unwrap());
+        started.assert();
+        stopped.assert();
+    }
+
+    #[actix_web::test]
+    async fn silent_udp_tier_is_given_up() {
+        let server = MockServer::start();
+        let backup = server.mock(|when, then| {
+            when.method(GET).path("/announce");
+            then.status(200).body("d8:intervali60e5:peers0:e");
+        });
+        // Bound but never answering, as a tracker whose packets are dropped.
+        let silent = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let silent_url =
+            Url::parse(&format!("udp://{}/announce", silent.local_addr().unwrap())).unwrap();
+
+        let trackers = [(silent_url, None), (announce_url(&server), None)];
+        let request = AnnounceRequest::new(&[0x05], "peer_id", 6881);
+        let result = announce_to_first(
+            &trackers,
+            request,
+            &HttpClient::default(),
+            Duration::from_millis(300),
+        )
+        .await;
+
+        assert_eq!(result.unwrap().0, announce_url(&server));
+        backup.assert();
+    }
+
+    #[actix_web::test]
+    async fn working_tracker_is_tried_first() {
+        let server = MockServer::start();
+        let failing = server.mock(|when, then| {
+            when.method(GET).path("/failing/announce");
+            then.status(200).body("d14:failure reason4:downe");
+        });
+        let working = server.mock(|when, then| {
+            when.method(GET).path("/announce");
+            then.status(200).body("d8:intervali1e5:peers0:e");
+        });
+        let backup = server.mock(|when, then| {
+            when.method(GET).path("/backup/announce");
+            then.status(200).body("d8:intervali1e5:peers0:e");
+        });
+
+        let failing_url = Url::parse(&server.url("/failing/announce")).unwrap();
+        let backup_url = Url::parse(&server.url("/backup/announce")).unwrap();
+        let _tracker = start_tracker(
+            vec![vec![failing_url, announce_url(&server)], vec![backup_url]],
+            &[0x03],
+        );
+        actix_web::rt::time::sleep(Duration::from_millis(1500)).await;
+
+        assert_eq!(failing.hits(), 1);
+        assert_eq!(working.hits(), 2);
+        assert_eq!(backup.hits(), 0);
+    }
+}
@@ -1,46 +1,132 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use actix::prelude::*;
+use log::{error, info};
 use url::Url;
 
-use super::{messages::TorrentRegistered, tracker::TrackerActor};
+use crate::common::{generator::generate_peer_id, hex};
+use crate::tracker::{
+    self, announce::AnnounceEvent, scrape::ScrapeResponse, tcp_tracker::HttpClient,
+    tiers::TrackerTiers, TrackerError,
+};
+
+use super::{
+    events::TorrentEventKind,
+    messages::{Announce, SwarmScraped, TorrentRegistered, TorrentStateChanged, TrackerAnswered},
+    torrent::TorrentActor,
+    tracker::TrackerActor,
+};
+
+// Torrents answered by the same tracker in a short time are scraped in the same request.
+const SCRAPE_DELAY: Duration = Duration::from_secs(5);
+const SCRAPE_INTERVAL: Duration = Duration::from_secs(30 * 60);
 
+// Starts the announces of every torrent to its own trackers. The torrents are scraped from
+// time to time, all those announced to the same tracker together.
 pub struct TrackersInterfaceActor {
-    trackers: Vec<Addr<TrackerActor>>,
+    default_trackers: Vec<Url>,
+    torrents: HashMap<Vec<u8>, RegisteredTorrent>,
+    port: u16,
+    // Peer id and key announced to every tracker for the whole session.
+    peer_id: String,
+    key: u32,
+    http: HttpClient,
+    next_scrape: Option<SpawnHandle>,
+}
+
+struct RegisteredTorrent {
+    torrent: Addr<TorrentActor>,
+    announcer: Addr<TrackerActor>,
+    // Tracker that answered the last announce, the one scraped.
+    tracker: Option<Url>,
 }
 
 impl TrackersInterfaceActor {
-    pub fn new() -> TrackersInterfaceActor {
-        let urls = vec![
-            "udp://93.158.213.92:1337/announce",
-            "udp://102.223.180.235:6969/announce",
-            "udp://23.134.88.6:1337/announce",
-            "udp://193.189.100.187:6969/announce",
-            "udp://185.243.218.213:80/announce",
-            "udp://91.216.110.52:451/announce",
-            "udp://208.83.20.20:6969/announce",
-            "udp://23.157.120.14:6969/announce",
-            "udp://156.234.201.18:80/announce",
-            "udp://185.102.219.163:6969/announce",
-            "udp://185.50.159.149:6969/announce",
-            "udp://209.141.59.16:6969/announce",
-            "udp://38.7.201.142:6969/announce",
-            "udp://73.170.204.100:6969/announce",
-            "udp://176.31.250.174:6969/announce",
-            "udp://82.156.24.219:6969/announce",
-            "udp://83.102.180.21:80/announce",
-            "udp://185.230.4.150:1337/announce",
-        ];
-        let mut trackers = vec![];
+    pub fn new(urls: &[String], port: u16) -> TrackersInterfaceActor {
+        let mut default_trackers = vec![];
 
         for url in urls {
-            let tracker = TrackerActor {
-                url: Url::parse(url).ok().unwrap(),
+            match Url::parse(url) {
+                Ok(url) => default_trackers.push(url),
+                Err(err) => error!("Discarded default tracker {:?} with error {:?}", url, err),
+            }
+        }
+
+        TrackersInterfaceActor {
+            default_trackers,
+            torrents: HashMap::new(),
+            port,
+            peer_id: generate_peer_id(),
+            key: rand::random(),
+            http: HttpClient::default(),
+            next_scrape: None,
+        }
+    }
+
+    // Announces and scrapes of HTTP trackers all go through this client.
+    pub fn http_client(mut self, http: HttpClient) -> Self {
+        self.http = http;
+        self
+    }
+
+    fn schedule_scrape(&mut self, delay: Duration, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.next_scrape.take() {
+            ctx.cancel_future(handle);
+        }
+        self.next_scrape = Some(ctx.run_later(delay, |actor, ctx| actor.scrape(ctx)));
+    }
+
+    // Scraping starts again with the next answer of a tracker when there is nothing to scrape.
+    fn scrape(&mut self, ctx: &mut Context<Self>) {
+        self.next_scrape = None;
+        self.torrents
+            .retain(|_, registered| registered.announcer.connected());
+
+        let mut batches: HashMap<Url, Vec<Vec<u8>>> = HashMap::new();
+        for (info_hash, registered) in self.torrents.iter() {
+            if let Some(url) = registered
+                .tracker
+                .as_ref()
+                .filter(|url| tracker::supports_scrape(url))
+            {
+                batches
+                    .entry(url.clone())
+                    .or_default()
+                    .push(info_hash.clone());
             }
-            .start();
+        }
+        if batches.is_empty() {
+            return;
+        }
 
-            trackers.push(tracker);
+        for (url, info_hashes) in batches {
+            let http = self.http.clone();
+            async move {
+                let result = tracker::scrape(&url, &info_hashes, &http).await;
+                (url, result)
+            }
+            .into_actor(self)
+            .map(|(url, result), actor, _ctx| actor.scraped(url, result))
+            .spawn(ctx);
         }
+        self.schedule_scrape(SCRAPE_INTERVAL, ctx);
+    }
 
-        TrackersInterfaceActor { trackers }
+    fn scraped(&mut self, url: Url, result: Result<ScrapeResponse, TrackerError>) {
+        match result {
+            Ok(response) => {
+                for (info_hash, stats) in response {
+                    if let Some(registered) = self.torrents.get(&info_hash) {
+                        registered.torrent.do_send(SwarmScraped {
+                            tracker: url.clone(),
+                            stats,
+                        });
+                    }
+                }
+            }
+            Err(err) => error!("Scrape of {:?} failed: {}", url.as_str(), err),
+        }
     }
 }
 
@@ -53,14 +139,223 @@ impl Actor for TrackersInterfaceActor {
     fn stopped(&mut self, _ctx: &mut Context<Self>) {}
 }
 
+// The default trackers are the last tier of every torrent but the private ones.
 impl Handler<TorrentRegistered> for TrackersInterfaceActor {
     type Result = Result<bool, std::io::Error>;
 
-    fn handle(&mut self, msg: TorrentRegistered, _ctx: &mut Context<Self>) -> Self::Result {
-        for actor in self.trackers.iter() {
-            let _ = actor.try_send(msg.clone());
+    fn handle(&mut self, msg: TorrentRegistered, ctx: &mut Context<Self>) -> Self::Result {
+        // The announcer of a removed torrent may still be there, sending the stopped event.
+        if let Some(registered) = self.torrents.get(&msg.info_hash) {
+            if registered.announcer.connected() {
+                return Ok(false);
+            }
+        }
+
+        let mut tiers = TrackerTiers::new(msg.tracker_tiers);
+        if !msg.private {
+            tiers = tiers.with_tier(self.default_trackers.clone());
+        }
+        if tiers.is_empty() {
+            info!(
+                "No tracker to announce torrent {:?}",
+                hex::encode(&msg.info_hash)
+            );
+            return Ok(false);
+        }
+
+        let announcer = TrackerActor::new(
+            msg.info_hash.clone(),
+            msg.torrent_actor_addr.clone(),
+            ctx.address().recipient(),
+            tiers.shuffle(),
+        )
+        .peer(self.port, self.peer_id.clone(), self.key)
+        .http_client(self.http.clone())
+        .start();
+
+        self.torrents.insert(
+            msg.info_hash,
+            RegisteredTorrent {
+                torrent: msg.torrent_actor_addr,
+                announcer,
+                tracker: None,
+            },
+        );
+
+        Ok(true)
+    }
+}
+
+// The first answer of a tracker brings the next scrape closer.
+impl Handler<TrackerAnswered> for TrackersInterfaceActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, msg: TrackerAnswered, ctx: &mut Context<Self>) -> Self::Result {
+        let Some(registered) = self.torrents.get_mut(&msg.info_hash) else {
+            return Ok(false);
+        };
+
+        if registered.tracker.replace(msg.tracker).is_none() {
+            self.schedule_scrape(SCRAPE_DELAY, ctx);
         }
 
         Ok(true)
     }
 }
+
+// Torrents tell when they complete, pause, resume or are removed.
+impl Handler<TorrentStateChanged> for TrackersInterfaceActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, msg: TorrentStateChanged, _ctx: &mut Context<Self>) -> Self::Result {
+        let event_to_announce = match msg.kind {
+            TorrentEventKind::TorrentCompleted => AnnounceEvent::Completed,
+            TorrentEventKind::TorrentResumed => AnnounceEvent::Started,
+            TorrentEventKind::TorrentPaused | TorrentEventKind::TorrentRemoved => {
+                AnnounceEvent::Stopped
+            }
+            _ => return Ok(false),
+        };
+        let Some(registered) = self.torrents.get_mut(&msg.info_hash) else {
+            return Ok(false);
+        };
+
+        // Stopped torrents are not scraped.
+        if event_to_announce == AnnounceEvent::Stopped {
+            registered.tracker = None;
+        }
+        registered.announcer.do_send(Announce {
+            event: event_to_announce,
+        });
+        if msg.kind == TorrentEventKind::TorrentRemoved {
+            self.torrents.remove(&msg.info_hash);
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        actors::{events::EventBus, messages::GetTorrentStatus},
+        config::Config,
+        tracker::scrape::ScrapeStats,
+    };
+
+    use httpmock::prelude::*;
+
+    fn register(
+        interface: &Addr<TrackersInterfaceActor>,
+        info_hash: &[u8],
+        tracker_tiers: Vec<Vec<Url>>,
+        private: bool,
+    ) -> Addr<TorrentActor> {
+        let torrent =
+            TorrentActor::new(info_hash.to_vec(), &Config::default(), EventBus::new()).start();
+        interface.do_send(TorrentRegistered {
+            info_hash: info_hash.to_vec(),
+            torrent_actor_addr: torrent.clone(),
+            tracker_tiers,
+            private,
+        });
+        torrent
+    }
+
+    #[actix_web::test]
+    async fn scraped_swarm_in_torrent_status() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/announce");
+            then.status(200).body("d8:intervali60e5:peers0:e");
+        });
+        let scrape = server.mock(|when, then| {
+            when.method(GET)
+                .path("/scrape")
+                .query_param("info_hash", "a")
+                .query_param("info_hash", "b");
+            then.status(200).body(
+                "d5:filesd1:ad8:completei1e10:downloadedi2e10:incompletei3ee\
+                 1:bd8:completei4e10:downloadedi5e10:incompletei6eeee",
+            );
+        });
+
+        let interface = TrackersInterfaceActor::new(&[], 6881).start();
+        let tracker = Url::parse(&server.url("/announce")).unwrap();
+        let torrent = register(&interface, b"a", vec![vec![tracker.clone()]], false);
+        register(&interface, b"b", vec![vec![tracker]], false);
+        actix_web::rt::time::sleep(SCRAPE_DELAY + Duration::from_millis(500)).await;
+        let status = torrent.send(GetTorrentStatus).await.unwrap();
+
+        scrape.assert();
+        assert_eq!(
+            status.swarm.get(&server.url("/announce")),
+            Some(&ScrapeStats {
+                complete: 1,
+                downloaded: 2,
+                incomplete: 3
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn paused_torrent_is_announced_stopped() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/announce")
+                .query_param("event", "started");
+            then.status(200).body("d8:intervali60e5:peers0:e");
+        });
+        let stopped = server.mock(|when, then| {
+            when.method(GET)
+                .path("/announce")
+                .query_param("event", "stopped");
+            then.status(200).body("d8:intervali60e5:peers0:e");
+        });
+
+        let interface = TrackersInterfaceActor::new(&[], 6881).start();
+        let tracker = Url::parse(&server.url("/announce")).unwrap();
+        register(&interface, b"a", vec![vec![tracker]], false);
+        actix_web::rt::time::sleep(Duration::from_millis(200)).await;
+        let announced = interface
+            .send(TorrentStateChanged {
+                info_hash: b"a".to_vec(),
+                kind: TorrentEventKind::TorrentPaused,
+            })
+            .await
+            .unwrap();
+        actix_web::rt::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(announced.unwrap());
+        stopped.assert();
+    }
+
+    #[actix_web::test]
+    async fn private_torrent_skips_default_trackers() {
+        let server = MockServer::start();
+        let own = server.mock(|when, then| {
+            when.method(GET).path("/own/announce");
+            then.status(200).body("d14:failure reason4:downe");
+        });
+        let default = server.mock(|when, then| {
+            when.method(GET).path("/announce");
+            then.status(200).body("d8:intervali60e5:peers0:e");
+        });
+
+        let interface = TrackersInterfaceActor::new(&[server.url("/announce")], 6881).start();
+        let own_tracker = Url::parse(&server.url("/own/announce")).unwrap();
+        register(&interface, b"a", vec![vec![own_tracker.clone()]], true);
+        actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+
+        assert_eq!(own.hits(), 1);
+        assert_eq!(default.hits(), 0);
+
+        register(&interface, b"b", vec![vec![own_tracker]], false);
+        actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+
+        assert_eq!(own.hits(), 2);
+        assert_eq!(default.hits(), 1);
+    }
+}
@@ -1,10 +1,20 @@
+use std::path::PathBuf;
+
 use actix::prelude::*;
 
-use crate::torrent::writer::write;
+use crate::torrent::writer::{remove, write};
+
+use super::messages::{PieceReady, PieceWritten, RemoveFiles};
 
-use super::messages::PieceReady;
+pub struct WriterActor {
+    output_dir: PathBuf,
+}
 
-pub struct WriterActor;
+impl WriterActor {
+    pub fn new(output_dir: PathBuf) -> WriterActor {
+        WriterActor { output_dir }
+    }
+}
 
 // Provide Actor implementation for our actor
 impl Actor for WriterActor {
@@ -19,7 +29,30 @@ impl Handler<PieceReady> for WriterActor {
     type Result = Result<bool, std::io::Error>;
 
     fn handle(&mut self, msg: PieceReady, _ctx: &mut Self::Context) -> Self::Result {
-        let _ = write(msg.piece, msg.piece_idx, msg.files, msg.piece_length);
+        let written = write(
+            msg.piece,
+            msg.piece_idx,
+            msg.files,
+            msg.piece_length,
+            &self.output_dir,
+        );
+
+        match written {
+            Ok(()) => msg.torrent_actor.do_send(PieceWritten {
+                piece_idx: msg.piece_idx,
+            }),
+            Err(err) => log::error!("Piece {:?} not written: {}", msg.piece_idx, err),
+        }
+
+        Ok(true)
+    }
+}
+
+impl Handler<RemoveFiles> for WriterActor {
+    type Result = Result<bool, std::io::Error>;
+
+    fn handle(&mut self, msg: RemoveFiles, _ctx: &mut Self::Context) -> Self::Result {
+        remove(&msg.files, &self.output_dir)?;
 
         Ok(true)
     }
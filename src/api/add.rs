@@ -0,0 +1,251 @@
+use actix::prelude::*;
+use actix_multipart::Multipart;
+use actix_web::{http::header::LOCATION, post, web, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use serde::Serialize;
+use url::Url;
+
+use crate::actors::messages::{GetTorrent, PeerFound, RegisterTorrent, TorrentRegistered};
+use crate::actors::torrent::TorrentActor;
+use crate::common::hex;
+use crate::torrent::magnet;
+use crate::torrent::metafile::Metafile;
+
+use super::error::ApiError;
+use super::AppState;
+
+#[derive(Serialize)]
+struct TorrentCreated {
+    info_hash: String,
+    status_url: String,
+}
+
+#[post("/add/magnet")]
+async fn add_magnet(
+    data: web::Data<AppState>,
+    magnet_raw: String,
+) -> Result<HttpResponse, ApiError> {
+    let magnet = magnet::parse_magnet(magnet_raw.trim().as_bytes().to_vec())?;
+    let info_hash = magnet.get_info_hash();
+    ensure_not_registered(&data, &info_hash).await?;
+
+    let addr = TorrentActor::with_magnet(&magnet, &data.config, data.events.clone())
+        .trackers_interface(data.trackers_interface.clone().recipient())
+        .start();
+    let tracker_tiers = magnet.get_tracker_tiers();
+    let response = register_torrent(&data, info_hash, addr.clone(), tracker_tiers, false).await?;
+
+    // Peers listed in the magnet are used right away, alongside the ones from the trackers.
+    for peer in magnet.get_peers() {
+        addr.do_send(PeerFound { peer });
+    }
+
+    Ok(response)
+}
+
+// Accept a .torrent file either as the raw body (application/x-bittorrent) or as the first
+// field of a multipart form. The info is already known, so no metadata is fetched from peers.
+#[post("/add/torrent")]
+async fn add_torrent(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse, ApiError> {
+    let bytes = read_torrent_payload(&req, payload)
+        .await
+        .map_err(|err| ApiError::InvalidPayload(err.to_string()))?;
+
+    let metafile = Metafile::from_bytes(bytes)?;
+    let info_hash = metafile.get_info_hash();
+    ensure_not_registered(&data, &info_hash).await?;
+
+    let addr = TorrentActor::with_metafile(&metafile, &data.config, data.events.clone())
+        .trackers_interface(data.trackers_interface.clone().recipient())
+        .start();
+    let private = metafile.get_info().is_private();
+    register_torrent(
+        &data,
+        info_hash,
+        addr,
+        metafile.get_tracker_tiers(),
+        private,
+    )
+    .await
+}
+
+async fn read_torrent_payload(
+    req: &HttpRequest,
+    payload: web::Payload,
+) -> Result<Vec<u8>, actix_web::Error> {
+    let is_multipart = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("multipart/form-data"));
+
+    if !is_multipart {
+        return Ok(payload.to_bytes().await?.to_vec());
+    }
+
+    let mut multipart = Multipart::new(req.headers(), payload);
+    let mut bytes = vec![];
+    if let Some(field) = multipart.next().await {
+        let mut field = field?;
+        while let Some(chunk) = field.next().await {
+            bytes.extend_from_slice(&chunk?);
+        }
+    }
+
+    Ok(bytes)
+}
+
+// Duplicates are refused before the torrent actor and its pools of threads are started.
+async fn ensure_not_registered(
+    data: &web::Data<AppState>,
+    info_hash: &[u8],
+) -> Result<(), ApiError> {
+    let registered = data
+        .torrents_registry
+        .send(GetTorrent {
+            info_hash: info_hash.to_vec(),
+        })
+        .await?;
+
+    match registered {
+        Some(_) => Err(ApiError::DuplicateTorrent(hex::encode(info_hash))),
+        None => Ok(()),
+    }
+}
+
+// The registry still refuses an info hash registered in the meantime by a concurrent
+// request. In that case the new actor is dropped before any tracker knows about it and
+// stops as soon as its address goes away.
+async fn register_torrent(
+    data: &web::Data<AppState>,
+    info_hash: Vec<u8>,
+    addr: Addr<TorrentActor>,
+    tracker_tiers: Vec<Vec<Url>>,
+    private: bool,
+) -> Result<HttpResponse, ApiError> {
+    let info_hash_hex = hex::encode(&info_hash);
+
+    let registered = data
+        .torrents_registry
+        .send(RegisterTorrent {
+            info_hash: info_hash.clone(),
+            torrent_actor_addr: addr.clone(),
+        })
+        .await??;
+
+    if !registered {
+        return Err(ApiError::DuplicateTorrent(info_hash_hex));
+    }
+
+    let msg = TorrentRegistered {
+        info_hash,
+        torrent_actor_addr: addr,
+        tracker_tiers,
+        private,
+    };
+    let _ = data.trackers_interface.try_send(msg);
+
+    let status_url = format!("/torrents/{}", info_hash_hex);
+    Ok(HttpResponse::Created()
+        .insert_header((LOCATION, status_url.clone()))
+        .json(TorrentCreated {
+            info_hash: info_hash_hex,
+            status_url,
+        }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    use crate::actors::events::EventBus;
+    use crate::actors::torrents_registry::TorrentsRegistryActor;
+    use crate::actors::trackers_interface::TrackersInterfaceActor;
+    use crate::config::Config;
+
+    const MAGNET: &str = "magnet:?xt=urn:btih:a6e449c2281e62edbf8cdb447413ca288cf0e568&dn=test";
+
+    fn app_state() -> web::Data<AppState> {
+        let config = Config::default();
+        let events = EventBus::new();
+
+        web::Data::new(AppState {
+            trackers_interface: TrackersInterfaceActor::new(&[], config.peers.port).start(),
+            torrents_registry: TorrentsRegistryActor::new().start(),
+            events,
+            config,
+        })
+    }
+
+    #[actix_web::test]
+    async fn add_same_magnet_twice() {
+        let app = test::init_service(App::new().app_data(app_state()).service(add_magnet)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/add/magnet")
+            .set_payload(MAGNET)
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "info_hash": "a6e449c2281e62edbf8cdb447413ca288cf0e568",
+                "status_url": "/torrents/a6e449c2281e62edbf8cdb447413ca288cf0e568"
+            })
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/add/magnet")
+            .set_payload(MAGNET)
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn add_invalid_magnet() {
+        let app = test::init_service(App::new().app_data(app_state()).service(add_magnet)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/add/magnet")
+            .set_payload("not a magnet")
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(
+            body,
+            serde_json::json!({"error": "Invalid magnet: No magnet found"})
+        );
+    }
+
+    #[actix_web::test]
+    async fn add_torrent_with_invalid_info() {
+        let app = test::init_service(App::new().app_data(app_state()).service(add_torrent)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/add/torrent")
+            .insert_header(("content-type", "application/x-bittorrent"))
+            .set_payload("d4:infod6:lengthi5e4:name1:a12:piece lengthi0e6:pieces0:ee")
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "error": "Invalid torrent file: Piece length must be greater than zero"
+            })
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use actix_web::{post, web, HttpResponse};
+use serde::Deserialize;
+
+use crate::torrent::builder::TorrentBuilder;
+
+use super::error::ApiError;
+use super::torrent_file_response;
+
+#[derive(Deserialize)]
+struct CreateRequest {
+    path: PathBuf,
+    #[serde(default)]
+    trackers: Vec<Vec<String>>,
+    piece_length: Option<usize>,
+    comment: Option<String>,
+    #[serde(default)]
+    private: bool,
+}
+
+// Create a .torrent from a path on the machine running the server. The torrent is returned
+// as the body, the info hash and the magnet link as headers.
+#[post("/create")]
+async fn create_torrent(request: web::Json<CreateRequest>) -> Result<HttpResponse, ApiError> {
+    let request = request.into_inner();
+
+    let mut builder = TorrentBuilder::new(request.path)
+        .private(request.private)
+        .creation_date(chrono::Utc::now().timestamp() as usize);
+    for tier in request.trackers {
+        builder = builder.announce_tier(tier);
+    }
+    if let Some(piece_length) = request.piece_length {
+        builder = builder.piece_length(piece_length);
+    }
+    if let Some(comment) = request.comment {
+        builder = builder.comment(comment);
+    }
+
+    // Hashing reads every file, so it is kept away from the server workers.
+    let metafile = web::block(move || builder.build()).await??;
+
+    Ok(torrent_file_response(HttpResponse::Created(), &metafile))
+}
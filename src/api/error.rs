@@ -0,0 +1,85 @@
+use actix::MailboxError;
+use actix_web::{error::BlockingError, http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+
+use crate::torrent::builder::BuilderError;
+use crate::torrent::magnet::MagnetError;
+use crate::torrent::metafile::MetafileError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApiError {
+    #[error("Invalid magnet: {0}")]
+    InvalidMagnet(#[from] MagnetError),
+    #[error("Invalid torrent file: {0}")]
+    InvalidTorrent(#[from] MetafileError),
+    #[error("Cannot create torrent: {0}")]
+    Builder(#[from] BuilderError),
+    #[error("Invalid payload: {0}")]
+    InvalidPayload(String),
+    #[error("Info hash must be hex encoded")]
+    InvalidInfoHash(),
+    #[error("Torrent {0} is already being downloaded")]
+    DuplicateTorrent(String),
+    #[error("Torrent not found")]
+    TorrentNotFound(),
+    #[error("Torrent metadata not fetched yet")]
+    MetadataNotFetched(),
+    #[error("Actor unavailable")]
+    Mailbox(#[from] MailboxError),
+    #[error("Error handling the torrent")]
+    Io(#[from] std::io::Error),
+    #[error("Task interrupted")]
+    Blocking(#[from] BlockingError),
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidMagnet(_)
+            | ApiError::InvalidTorrent(_)
+            | ApiError::Builder(_)
+            | ApiError::InvalidPayload(_)
+            | ApiError::InvalidInfoHash() => StatusCode::BAD_REQUEST,
+            ApiError::DuplicateTorrent(_) => StatusCode::CONFLICT,
+            ApiError::TorrentNotFound() | ApiError::MetadataNotFetched() => StatusCode::NOT_FOUND,
+            ApiError::Mailbox(_) | ApiError::Io(_) | ApiError::Blocking(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: self.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[actix_web::test]
+    async fn invalid_magnet_response() {
+        let response = ApiError::from(MagnetError::NoMagnet()).error_response();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            to_bytes(response.into_body()).await.unwrap(),
+            r#"{"error":"Invalid magnet: No magnet found"}"#
+        );
+    }
+
+    #[test]
+    fn duplicate_torrent_status() {
+        let error = ApiError::DuplicateTorrent("aa".to_owned());
+
+        assert_eq!(error.status_code(), StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,48 @@
+use actix_web::{get, web, HttpResponse};
+use futures_util::{stream, StreamExt};
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::AppState;
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    info_hash: Option<String>,
+}
+
+// Server-Sent Events stream of the torrents progress, optionally filtered by the hex
+// encoded info hash of a single torrent.
+#[get("/events")]
+async fn stream_events(data: web::Data<AppState>, query: web::Query<EventsQuery>) -> HttpResponse {
+    let filter = query.into_inner().info_hash.map(|hash| hash.to_lowercase());
+    let receiver = data.events.subscribe();
+
+    let events = stream::unfold(receiver, move |mut receiver| {
+        let filter = filter.clone();
+        async move {
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                };
+
+                if filter.as_ref().is_some_and(|hash| *hash != event.info_hash) {
+                    continue;
+                }
+
+                let payload = serde_json::to_string(&event).unwrap_or_default();
+                let chunk = web::Bytes::from(format!("data: {payload}\n\n"));
+                return Some((Ok::<_, actix_web::Error>(chunk), receiver));
+            }
+        }
+    });
+
+    // A first comment line makes the response headers reach the client straight away.
+    let opening = stream::once(async { Ok(web::Bytes::from_static(b": connected\n\n")) });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(opening.chain(events))
+}
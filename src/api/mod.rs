@@ -0,0 +1,53 @@
+mod add;
+mod create;
+mod error;
+mod events;
+mod torrents;
+
+use actix::prelude::*;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse, HttpResponseBuilder};
+
+use crate::actors::{
+    events::EventBus, torrents_registry::TorrentsRegistryActor,
+    trackers_interface::TrackersInterfaceActor,
+};
+use crate::bencode::encode::Encode;
+use crate::common::hex;
+use crate::config::Config;
+use crate::torrent::metafile::Metafile;
+
+pub struct AppState {
+    pub trackers_interface: Addr<TrackersInterfaceActor>,
+    pub torrents_registry: Addr<TorrentsRegistryActor>,
+    pub events: EventBus,
+    pub config: Config,
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(add::add_magnet)
+        .service(add::add_torrent)
+        .service(create::create_torrent)
+        .service(torrents::list_torrents)
+        .service(torrents::get_torrent)
+        .service(torrents::export_torrent)
+        .service(torrents::pause_torrent)
+        .service(torrents::resume_torrent)
+        .service(torrents::remove_torrent)
+        .service(events::stream_events);
+}
+
+// The .torrent is sent as an attachment, with the info hash and the magnet link as headers.
+fn torrent_file_response(mut response: HttpResponseBuilder, metafile: &Metafile) -> HttpResponse {
+    let filename = format!("{}.torrent", metafile.get_info().get_name());
+
+    response
+        .content_type("application/x-bittorrent")
+        .insert_header(("X-Info-Hash", hex::encode(&metafile.get_info_hash())))
+        .insert_header(("X-Magnet-Link", metafile.get_magnet_link()))
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        .body(metafile.encode())
+}
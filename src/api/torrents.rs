@@ -0,0 +1,132 @@
+use actix::prelude::*;
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+
+use crate::actors::messages::{
+    GetTorrent, GetTorrentFile, GetTorrentStatus, GetTorrents, PauseTorrent, RemoveTorrent,
+    ResumeTorrent, UnregisterTorrent,
+};
+use crate::actors::torrent::TorrentActor;
+use crate::common::hex;
+
+use super::error::ApiError;
+use super::{torrent_file_response, AppState};
+
+#[derive(Deserialize)]
+struct RemoveQuery {
+    #[serde(default)]
+    delete_data: bool,
+}
+
+#[get("/torrents")]
+async fn list_torrents(data: web::Data<AppState>) -> HttpResponse {
+    let torrents = data
+        .torrents_registry
+        .send(GetTorrents)
+        .await
+        .unwrap_or_default();
+
+    let mut statuses = vec![];
+    for torrent in torrents {
+        if let Ok(status) = torrent.send(GetTorrentStatus).await {
+            statuses.push(status);
+        }
+    }
+
+    HttpResponse::Ok().json(statuses)
+}
+
+#[get("/torrents/{info_hash}")]
+async fn get_torrent(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let (_, torrent) = find_torrent(&data, &path).await?;
+    torrent_status(&torrent).await
+}
+
+#[get("/torrents/{info_hash}/torrent")]
+async fn export_torrent(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let (_, torrent) = find_torrent(&data, &path).await?;
+
+    let metafile = torrent
+        .send(GetTorrentFile)
+        .await
+        .map_err(|_| ApiError::TorrentNotFound())?
+        .ok_or(ApiError::MetadataNotFetched())?;
+
+    Ok(torrent_file_response(HttpResponse::Ok(), &metafile))
+}
+
+#[post("/torrents/{info_hash}/pause")]
+async fn pause_torrent(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let (_, torrent) = find_torrent(&data, &path).await?;
+
+    let _ = torrent.send(PauseTorrent).await;
+    torrent_status(&torrent).await
+}
+
+#[post("/torrents/{info_hash}/resume")]
+async fn resume_torrent(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let (_, torrent) = find_torrent(&data, &path).await?;
+
+    let _ = torrent.send(ResumeTorrent).await;
+    torrent_status(&torrent).await
+}
+
+#[delete("/torrents/{info_hash}")]
+async fn remove_torrent(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<RemoveQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let (info_hash, torrent) = find_torrent(&data, &path).await?;
+
+    let _ = data
+        .torrents_registry
+        .send(UnregisterTorrent { info_hash })
+        .await;
+    let _ = torrent
+        .send(RemoveTorrent {
+            delete_data: query.delete_data,
+        })
+        .await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn find_torrent(
+    data: &web::Data<AppState>,
+    info_hash_hex: &str,
+) -> Result<(Vec<u8>, Addr<TorrentActor>), ApiError> {
+    let info_hash = hex::decode(info_hash_hex).ok_or(ApiError::InvalidInfoHash())?;
+
+    let torrent = data
+        .torrents_registry
+        .send(GetTorrent {
+            info_hash: info_hash.clone(),
+        })
+        .await?
+        .ok_or(ApiError::TorrentNotFound())?;
+
+    Ok((info_hash, torrent))
+}
+
+// A torrent removed in the meantime has its actor stopped, hence it is reported as not found.
+async fn torrent_status(torrent: &Addr<TorrentActor>) -> Result<HttpResponse, ApiError> {
+    let status = torrent
+        .send(GetTorrentStatus)
+        .await
+        .map_err(|_| ApiError::TorrentNotFound())?;
+
+    Ok(HttpResponse::Ok().json(status))
+}
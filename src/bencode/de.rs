@@ -0,0 +1,339 @@
+use serde::de::{self, Deserialize, DeserializeOwned, DeserializeSeed, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::bencode::decode::Decoder;
+use crate::bencode::error::BencodeError;
+use crate::bencode::view::{View, ViewDecoder, ViewValue};
+
+// Deserialize a value taking all the given bytes.
+#[cfg(test)]
+pub fn from_bytes<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, BencodeError> {
+    let (value, parsed_bytes) = from_bytes_prefix(bytes)?;
+
+    if parsed_bytes != bytes.len() {
+        return Err(BencodeError::TrailingBytes(parsed_bytes));
+    }
+    Ok(value)
+}
+
+// Deserialize the value found at the start of the bytes, returned together with its length.
+// Useful when the bencode is followed by other data, as in the ut_metadata messages.
+pub fn from_bytes_prefix<'a, T: Deserialize<'a>>(
+    bytes: &'a [u8],
+) -> Result<(T, usize), BencodeError> {
+    let mut decoder = ViewDecoder::new(bytes);
+    let view = decoder.decode()?.ok_or(BencodeError::NoValue())?;
+    Ok((from_view(view)?, decoder.get_total_parsed_bytes()))
+}
+
+// Deserialize the next value of a decoder, e.g. one with limits for untrusted input.
+pub fn from_decoder<T: DeserializeOwned>(decoder: &mut Decoder) -> Result<T, BencodeError> {
+    let view = decoder.decode_view()?.ok_or(BencodeError::NoValue())?;
+    from_view(view)
+}
+
+// Strings and byte strings borrow from the input of the view when the type allows it.
+pub fn from_view<'a, T: Deserialize<'a>>(view: View<'a>) -> Result<T, BencodeError> {
+    T::deserialize(view)
+}
+
+// Strings, dictionary keys included, are given to the visitor as text when valid UTF-8 and
+// as bytes otherwise. Types expecting bytes, see the bytes module, always get them as they are.
+impl<'de> de::Deserializer<'de> for View<'de> {
+    type Error = BencodeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self.into_value() {
+            ViewValue::Integer(value) => visitor.visit_i64(value),
+            ViewValue::String(value) => match std::str::from_utf8(value) {
+                Ok(value) => visitor.visit_borrowed_str(value),
+                Err(_) => visitor.visit_borrowed_bytes(value),
+            },
+            ViewValue::List(values) => visitor.visit_seq(SeqDeserializer {
+                iter: values.into_iter(),
+            }),
+            ViewValue::Dictionary(entries) => visitor.visit_map(MapDeserializer {
+                iter: entries.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self.into_value() {
+            ViewValue::Integer(value) => visitor.visit_bool(value != 0),
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        match self.into_value() {
+            ViewValue::String(value) => visitor.visit_borrowed_bytes(value),
+            other => Err(de::Error::invalid_type(unexpected(&other), &visitor)),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    // A value is there, missing keys are handled as None by serde itself.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BencodeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, BencodeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Unit variants are strings, the others a dictionary with the variant name as only key.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, BencodeError> {
+        match self.clone().into_value() {
+            ViewValue::String(_) => visitor.visit_enum(EnumDeserializer {
+                variant: self,
+                value: None,
+            }),
+            ViewValue::Dictionary(entries) => {
+                let mut entries = entries.into_iter();
+                match (entries.next(), entries.next()) {
+                    (Some((variant, value)), None) => visitor.visit_enum(EnumDeserializer {
+                        variant,
+                        value: Some(value),
+                    }),
+                    _ => Err(de::Error::invalid_length(0, &"a single variant")),
+                }
+            }
+            other => Err(de::Error::invalid_type(unexpected(&other), &"an enum")),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+fn unexpected<'a>(value: &ViewValue<'a>) -> de::Unexpected<'a> {
+    match value {
+        ViewValue::Integer(value) => de::Unexpected::Signed(*value),
+        ViewValue::String(value) => de::Unexpected::Bytes(value),
+        ViewValue::List(_) => de::Unexpected::Seq,
+        ViewValue::Dictionary(_) => de::Unexpected::Map,
+    }
+}
+
+struct SeqDeserializer<'de> {
+    iter: std::vec::IntoIter<View<'de>>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqDeserializer<'de> {
+    type Error = BencodeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, BencodeError> {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer<'de> {
+    iter: std::vec::IntoIter<(View<'de>, View<'de>)>,
+    value: Option<View<'de>>,
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer<'de> {
+    type Error = BencodeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, BencodeError> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, BencodeError> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(BencodeError::Custom(
+                "Value requested before its key".to_owned(),
+            )),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumDeserializer<'de> {
+    variant: View<'de>,
+    value: Option<View<'de>>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer<'de> {
+    type Error = BencodeError;
+    type Variant = VariantDeserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer<'de>), BencodeError> {
+        let variant = seed.deserialize(self.variant)?;
+        Ok((variant, VariantDeserializer { value: self.value }))
+    }
+}
+
+struct VariantDeserializer<'de> {
+    value: Option<View<'de>>,
+}
+
+impl VariantDeserializer<'_> {
+    fn missing_value() -> BencodeError {
+        de::Error::invalid_type(de::Unexpected::UnitVariant, &"a variant with a value")
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer<'de> {
+    type Error = BencodeError;
+
+    fn unit_variant(self) -> Result<(), BencodeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, BencodeError> {
+        let value = self.value.ok_or_else(Self::missing_value)?;
+        seed.deserialize(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, BencodeError> {
+        let value = self.value.ok_or_else(Self::missing_value)?;
+        de::Deserializer::deserialize_seq(value, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, BencodeError> {
+        let value = self.value.ok_or_else(Self::missing_value)?;
+        de::Deserializer::deserialize_map(value, visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::bencode::bytes;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Sample {
+        name: String,
+        #[serde(rename = "piece length")]
+        piece_length: usize,
+        #[serde(with = "bytes")]
+        pieces: Vec<u8>,
+        comment: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        private: bool,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Event {
+        Started,
+        Completed(usize),
+    }
+
+    #[test]
+    fn deserialize_struct() {
+        let bytes = [
+            "d4:name1:a12:piece lengthi4e6:pieces2:".as_bytes(),
+            &[0xff, 0x00],
+            "7:privatei1e7:unknownli1eee".as_bytes(),
+        ]
+        .concat();
+
+        let sample: Sample = from_bytes(&bytes).unwrap();
+
+        assert_eq!(
+            sample,
+            Sample {
+                name: "a".to_owned(),
+                piece_length: 4,
+                pieces: vec![0xff, 0x00],
+                comment: None,
+                tags: vec![],
+                private: true,
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_map_and_enum() {
+        let map: HashMap<String, u8> = from_bytes(b"d1:ai2e2:zzi1ee").unwrap();
+        assert_eq!(
+            map,
+            HashMap::from([("a".to_owned(), 2), ("zz".to_owned(), 1)])
+        );
+
+        let events: Vec<Event> = from_bytes(b"l7:Startedd9:Completedi3eee").unwrap();
+        assert_eq!(events, vec![Event::Started, Event::Completed(3)]);
+    }
+
+    #[test]
+    fn deserialize_with_trailing_bytes() {
+        let bytes = b"d5:piecei2eeDATA";
+
+        let result: Result<HashMap<String, usize>, _> = from_bytes(bytes);
+        assert_eq!(result, Err(BencodeError::TrailingBytes(12)));
+
+        let (map, length): (HashMap<String, usize>, usize) = from_bytes_prefix(bytes).unwrap();
+        assert_eq!(map, HashMap::from([("piece".to_owned(), 2)]));
+        assert_eq!(&bytes[length..], b"DATA");
+    }
+
+    #[test]
+    fn deserialize_wrong_type() {
+        let result: Result<Sample, _> = from_bytes(b"l1:ae");
+
+        assert!(matches!(result, Err(BencodeError::Custom(_))));
+        assert_eq!(from_bytes::<u8>(b""), Err(BencodeError::NoValue()));
+    }
+}
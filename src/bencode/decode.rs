@@ -1,138 +1,138 @@
-use std::collections::HashMap;
-use std::str;
-
-use log::error;
-
 use crate::bencode::metainfo::Metainfo;
+use crate::bencode::view::{View, ViewDecoder};
 
+// Nesting allowed by default, far beyond what torrents and peer messages need.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+// Decoder over owned bytes, values are copied out as Metainfo. See ViewDecoder to borrow
+// them from the input instead.
 pub struct Decoder {
     current: usize,
     contents: Vec<u8>,
+    options: DecoderOptions,
 }
 
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum DecoderError {
-    #[error("Bad number from string at position: {0}")]
-    BadNumberFromString(usize),
-    #[error("Bad number decode from: {0}")]
-    BadNumberDecode(String),
-    #[error("Expected to parse a string at position: {0}")]
-    NoStringFound(usize),
-    #[error("Bad string from bytes conversion at position: {0}")]
-    BadStringFromBytes(usize),
+    #[error("Unexpected end of input at position {0}, expected {1}")]
+    UnexpectedEnd(usize, &'static str),
+    #[error("Unexpected byte {1:#04x} at position {0}, expected {2}")]
+    UnexpectedByte(usize, u8, &'static str),
+    #[error("Invalid integer at position {0}: {1}")]
+    InvalidInteger(usize, &'static str),
+    #[error("Dictionary key at position {0} is not sorted or is repeated")]
+    UnsortedKey(usize),
+    #[error("Nesting deeper than {1} levels at position {0}")]
+    DepthLimit(usize, usize),
+    #[error("Value longer than {1} bytes at position {0}")]
+    SizeLimit(usize, usize),
+    #[error("Expected to parse a dictionary at position: {0}")]
+    NoDictionaryFound(usize),
 }
 
-impl Decoder {
-    pub fn init(source: Vec<u8>) -> Decoder {
-        Decoder {
-            current: 0,
-            contents: source,
+// Validation applied by every decoder. In strict mode only the canonical encoding of BEP 3
+// is accepted, the limits protect against hostile input.
+#[derive(Clone, Copy, Debug)]
+pub struct DecoderOptions {
+    strict: bool,
+    max_depth: usize,
+    max_size: usize,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> DecoderOptions {
+        DecoderOptions {
+            strict: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_size: usize::MAX,
         }
     }
+}
 
-    fn advance(&mut self) -> usize {
-        self.current += 1;
-        self.current
+impl DecoderOptions {
+    // Refuse leading zeros, negative zero and dictionary keys that are not sorted or repeated.
+    pub fn strict(mut self) -> DecoderOptions {
+        self.strict = true;
+        self
     }
 
-    fn get_current_byte(&self) -> u8 {
-        self.contents[self.current]
+    pub fn max_depth(mut self, max_depth: usize) -> DecoderOptions {
+        self.max_depth = max_depth;
+        self
     }
 
-    fn get_consecutive_digits(&mut self) -> Result<usize, DecoderError> {
-        let start = self.current;
-        while self.get_current_byte().is_ascii_digit() {
-            self.advance();
-        }
-        let end = self.current;
-
-        let string_integer = match str::from_utf8(&self.contents[start..end]) {
-            Ok(result) => result,
-            Err(_) => {
-                error!(
-                    "Error during string conversion at position {:?}",
-                    self.current
-                );
-                return Err(DecoderError::BadNumberFromString(self.current));
-            }
-        };
-
-        match string_integer.parse() {
-            Ok(result) => Ok(result),
-            Err(_) => {
-                error!(
-                    "Error during integer conversion at position {:?}",
-                    self.current
-                );
-                Err(DecoderError::BadNumberDecode(string_integer.to_owned()))
-            }
-        }
+    // Limit on the bytes taken by each decoded value.
+    pub fn max_size(mut self, max_size: usize) -> DecoderOptions {
+        self.max_size = max_size;
+        self
     }
 
-    fn parse_integer(&mut self) -> Result<Metainfo, DecoderError> {
-        self.advance();
-        let integer: usize = self.get_consecutive_digits()?;
-
-        match self.contents.get(self.current) {
-            Some(b'e') => self.advance(),
-            _ => self.advance(),
-        };
-        Ok(Metainfo::Integer(integer))
+    pub fn is_strict(&self) -> bool {
+        self.strict
     }
 
-    fn parse_string(&mut self) -> Result<Metainfo, DecoderError> {
-        let integer = self.get_consecutive_digits()?;
-        // TODO: Mettere check semicolon
-        self.advance();
-
-        let b = &self.contents[self.current..self.current + integer];
-        self.current += integer;
-        Ok(Metainfo::String(b.to_vec()))
+    pub fn get_max_depth(&self) -> usize {
+        self.max_depth
     }
 
-    fn parse_list(&mut self) -> Result<Metainfo, DecoderError> {
-        self.advance();
-        let mut list: Vec<Metainfo> = Vec::new();
+    pub fn get_max_size(&self) -> usize {
+        self.max_size
+    }
+}
 
-        while self.get_current_byte() != b'e' {
-            list.push(self.decode()?);
+impl Decoder {
+    pub fn init(source: Vec<u8>) -> Decoder {
+        Decoder {
+            current: 0,
+            contents: source,
+            options: DecoderOptions::default(),
         }
+    }
 
-        self.advance();
-        Ok(Metainfo::List(list))
+    pub fn strict(mut self) -> Decoder {
+        self.options = self.options.strict();
+        self
     }
 
-    fn parse_dictionary(&mut self) -> Result<Metainfo, DecoderError> {
-        self.advance();
+    pub fn max_depth(mut self, max_depth: usize) -> Decoder {
+        self.options = self.options.max_depth(max_depth);
+        self
+    }
 
-        let mut dictionary = HashMap::new();
-        while self.get_current_byte() != b'e' {
-            let key = if let Metainfo::String(raw_key) = self.parse_string()? {
-                str::from_utf8(&raw_key)
-                    .map_err(|_| DecoderError::BadStringFromBytes(self.current))?
-                    .to_owned()
-            } else {
-                return Err(DecoderError::NoStringFound(self.current));
-            };
-            let value = self.decode()?;
-            dictionary.insert(key, value);
-        }
+    pub fn max_size(mut self, max_size: usize) -> Decoder {
+        self.options = self.options.max_size(max_size);
+        self
+    }
+
+    // Decode the next value as a view borrowing from the decoder.
+    pub fn decode_view(&mut self) -> Result<Option<View<'_>>, DecoderError> {
+        let mut decoder = ViewDecoder::new(&self.contents)
+            .with_options(self.options)
+            .starting_at(self.current);
 
-        self.advance();
-        Ok(Metainfo::Dictionary(dictionary))
+        let view = decoder.decode()?;
+        self.current = decoder.get_total_parsed_bytes();
+        Ok(view)
     }
 
+    // Decode the next value, Metainfo::Nothing is returned once the input is over.
     pub fn decode(&mut self) -> Result<Metainfo, DecoderError> {
-        let result = match self.contents.get(self.current) {
-            None => Metainfo::Nothing(),
-            Some(b'i') => self.parse_integer()?,
-            Some(b'l') => self.parse_list()?,
-            Some(b'd') => self.parse_dictionary()?,
-            Some(b'1'..=b'9') => self.parse_string()?,
-            Some(_) => Metainfo::Nothing(),
-        };
+        Ok(self
+            .decode_view()?
+            .map_or(Metainfo::Nothing(), |view| view.to_metainfo()))
+    }
+
+    // Raw bytes of the value stored at the given key of the dictionary found at the current
+    // position, e.g. to hash the info dictionary exactly as it was received.
+    pub fn get_raw_value_from_dict(&mut self, key: &str) -> Result<Option<Vec<u8>>, DecoderError> {
+        if self.contents.get(self.current) != Some(&b'd') {
+            return Err(DecoderError::NoDictionaryFound(self.current));
+        }
 
-        Ok(result)
+        let span = self
+            .decode_view()?
+            .and_then(|view| view.get(key.as_bytes()).map(|value| value.get_span()));
+        Ok(span.map(|span| self.contents[span].to_vec()))
     }
 
     pub fn get_total_parsed_bytes(&self) -> usize {
@@ -143,6 +143,7 @@ impl Decoder {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::bencode::dictionary::Dictionary;
 
     #[test]
     fn decode_simple_string() {
@@ -181,7 +182,7 @@ mod test {
 
         assert_eq!(
             output,
-            Metainfo::Dictionary(HashMap::from([("miao".to_owned(), Metainfo::Integer(38))]))
+            Metainfo::Dictionary(Dictionary::from([("miao", Metainfo::Integer(38))]))
         );
     }
 
@@ -192,14 +193,11 @@ mod test {
 
         assert_eq!(
             output,
-            Metainfo::Dictionary(HashMap::from([
-                ("miao".to_owned(), Metainfo::Integer(38)),
+            Metainfo::Dictionary(Dictionary::from([
+                ("miao", Metainfo::Integer(38)),
                 (
-                    "info".to_owned(),
-                    Metainfo::Dictionary(HashMap::from([(
-                        "peers".to_owned(),
-                        Metainfo::Integer(18)
-                    )]))
+                    "info",
+                    Metainfo::Dictionary(Dictionary::from([("peers", Metainfo::Integer(18))]))
                 )
             ]))
         );
@@ -212,17 +210,121 @@ mod test {
 
         assert_eq!(
             first_output,
-            Metainfo::Dictionary(HashMap::from([("foo".to_owned(), Metainfo::Integer(32))]))
+            Metainfo::Dictionary(Dictionary::from([("foo", Metainfo::Integer(32))]))
         );
 
         let second_output = decoder.decode().unwrap();
 
         assert_eq!(
             second_output,
-            Metainfo::Dictionary(HashMap::from([(
-                "bar".to_owned(),
+            Metainfo::Dictionary(Dictionary::from([(
+                "bar",
                 Metainfo::String("boo".to_owned().as_bytes().to_vec())
             )]))
         );
     }
+
+    #[test]
+    fn raw_value_from_dictionary() {
+        let bytes = "d4:miaoi38e4:infod5:peersi18e6:sourcel1:aeee".as_bytes();
+
+        let raw = Decoder::init(bytes.to_vec())
+            .get_raw_value_from_dict("info")
+            .unwrap();
+        assert_eq!(raw, Some("d5:peersi18e6:sourcel1:aee".as_bytes().to_vec()));
+
+        let missing = Decoder::init(bytes.to_vec())
+            .get_raw_value_from_dict("announce")
+            .unwrap();
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn raw_value_from_non_dictionary() {
+        let result = Decoder::init("l4:infoe".as_bytes().to_vec()).get_raw_value_from_dict("info");
+
+        assert_eq!(result, Err(DecoderError::NoDictionaryFound(0)));
+    }
+
+    #[test]
+    fn decode_negative_integer_and_empty_string() {
+        let mut decoder = Decoder::init("li-42e0:i9223372036854775807ee".as_bytes().to_vec());
+
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Metainfo::List(vec![
+                Metainfo::Integer(-42),
+                Metainfo::String(vec![]),
+                Metainfo::Integer(i64::MAX)
+            ])
+        );
+    }
+
+    #[test]
+    fn decode_malformed_input() {
+        let cases = [
+            (
+                "i12x",
+                DecoderError::UnexpectedByte(3, b'x', "end of integer"),
+            ),
+            ("i12", DecoderError::UnexpectedEnd(3, "end of integer")),
+            ("ie", DecoderError::UnexpectedByte(1, b'e', "a digit")),
+            (
+                "i9223372036854775808e",
+                DecoderError::InvalidInteger(0, "out of range"),
+            ),
+            ("5:abc", DecoderError::UnexpectedEnd(5, "string content")),
+            (
+                "3abc",
+                DecoderError::UnexpectedByte(1, b'a', "string length separator"),
+            ),
+            (
+                "l4:miao",
+                DecoderError::UnexpectedEnd(7, "a list item or its end"),
+            ),
+            ("di1ei2ee", DecoderError::UnexpectedByte(1, b'i', "a digit")),
+            ("x", DecoderError::UnexpectedByte(0, b'x', "a value")),
+        ];
+
+        for (input, error) in cases {
+            let result = Decoder::init(input.as_bytes().to_vec()).decode();
+            assert_eq!(result, Err(error), "decoding {:?}", input);
+        }
+    }
+
+    #[test]
+    fn decode_non_canonical_input() {
+        let cases = [
+            ("i03e", DecoderError::InvalidInteger(1, "leading zero")),
+            ("i-0e", DecoderError::InvalidInteger(0, "negative zero")),
+            ("03:abc", DecoderError::InvalidInteger(0, "leading zero")),
+            ("d1:bi1e1:ai2ee", DecoderError::UnsortedKey(7)),
+            ("d1:ai1e1:ai2ee", DecoderError::UnsortedKey(7)),
+        ];
+
+        for (input, error) in cases {
+            let lenient = Decoder::init(input.as_bytes().to_vec()).decode();
+            assert!(lenient.is_ok(), "decoding {:?}", input);
+
+            let strict = Decoder::init(input.as_bytes().to_vec()).strict().decode();
+            assert_eq!(strict, Err(error), "decoding {:?}", input);
+        }
+    }
+
+    #[test]
+    fn decode_with_limits() {
+        let nested = "lllleeee".as_bytes().to_vec();
+        assert!(Decoder::init(nested.to_vec()).max_depth(4).decode().is_ok());
+        assert_eq!(
+            Decoder::init(nested).max_depth(3).decode(),
+            Err(DecoderError::DepthLimit(3, 3))
+        );
+
+        let long = "l3:abc3:defe".as_bytes().to_vec();
+        assert!(Decoder::init(long.to_vec()).max_size(12).decode().is_ok());
+        assert_eq!(
+            Decoder::init(long).max_size(8).decode(),
+            Err(DecoderError::SizeLimit(0, 8))
+        );
+    }
 }
@@ -0,0 +1,163 @@
+use crate::bencode::encode::Encode;
+use crate::bencode::metainfo::Metainfo;
+
+// Bencode dictionary with byte string keys, kept in the order they were inserted so that a
+// decoded value is encoded back to the very same bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Dictionary {
+    entries: Vec<(Vec<u8>, Metainfo)>,
+}
+
+impl Dictionary {
+    pub fn new() -> Dictionary {
+        Dictionary::default()
+    }
+
+    // A key already present keeps its position and gets the new value.
+    pub fn insert(&mut self, key: impl Into<Vec<u8>>, value: Metainfo) -> Option<Metainfo> {
+        let key = key.into();
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, current)) => Some(std::mem::replace(current, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    // A new key goes before the first greater one, so that a sorted dictionary stays sorted.
+    pub fn insert_sorted(&mut self, key: impl Into<Vec<u8>>, value: Metainfo) -> Option<Metainfo> {
+        let key = key.into();
+        if self.get(&key).is_some() {
+            return self.insert(key, value);
+        }
+
+        let position = self
+            .entries
+            .iter()
+            .position(|(k, _)| *k > key)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(position, (key, value));
+        None
+    }
+
+    pub fn remove(&mut self, key: impl AsRef<[u8]>) -> Option<Metainfo> {
+        let position = self.entries.iter().position(|(k, _)| k == key.as_ref())?;
+        Some(self.entries.remove(position).1)
+    }
+
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&Metainfo> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key.as_ref())
+            .map(|(_, value)| value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &Metainfo)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_slice(), value))
+    }
+
+    // Canonical form required by BEP 3: keys sorted by their raw bytes, in every nested
+    // dictionary as well.
+    pub fn sort_keys(&mut self) {
+        self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, value) in self.entries.iter_mut() {
+            value.sort_keys();
+        }
+    }
+}
+
+impl IntoIterator for Dictionary {
+    type Item = (Vec<u8>, Metainfo);
+    type IntoIter = std::vec::IntoIter<(Vec<u8>, Metainfo)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<K: Into<Vec<u8>>> FromIterator<(K, Metainfo)> for Dictionary {
+    fn from_iter<I: IntoIterator<Item = (K, Metainfo)>>(iter: I) -> Dictionary {
+        let mut dictionary = Dictionary::new();
+        for (key, value) in iter {
+            dictionary.insert(key, value);
+        }
+        dictionary
+    }
+}
+
+impl<K: Into<Vec<u8>>, const N: usize> From<[(K, Metainfo); N]> for Dictionary {
+    fn from(entries: [(K, Metainfo); N]) -> Dictionary {
+        entries.into_iter().collect()
+    }
+}
+
+impl Encode for Dictionary {
+    fn encode(&self) -> Vec<u8> {
+        let mut acc = vec![b'd'];
+        for (key, value) in self.iter() {
+            acc.append(&mut key.to_vec().encode());
+            acc.append(&mut value.encode());
+        }
+        acc.push(b'e');
+        acc
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_keeps_order() {
+        let mut dictionary =
+            Dictionary::from([("b", Metainfo::Integer(1)), ("a", Metainfo::Integer(2))]);
+        dictionary.insert(vec![0xff], Metainfo::Integer(3));
+
+        assert_eq!(
+            dictionary.insert("b", Metainfo::Integer(4)),
+            Some(Metainfo::Integer(1))
+        );
+        assert_eq!(dictionary.get([0xff]), Some(&Metainfo::Integer(3)));
+
+        let keys: Vec<&[u8]> = dictionary.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec![b"b".as_slice(), b"a", &[0xff]]);
+        assert_eq!(dictionary.encode(), b"d1:bi4e1:ai2e1:\xffi3ee");
+    }
+
+    #[test]
+    fn insert_sorted_and_remove() {
+        let mut dictionary =
+            Dictionary::from([("a", Metainfo::Integer(1)), ("c", Metainfo::Integer(3))]);
+
+        assert_eq!(dictionary.insert_sorted("b", Metainfo::Integer(2)), None);
+        assert_eq!(
+            dictionary.insert_sorted("c", Metainfo::Integer(4)),
+            Some(Metainfo::Integer(3))
+        );
+        assert_eq!(dictionary.encode(), b"d1:ai1e1:bi2e1:ci4ee");
+
+        assert_eq!(dictionary.remove("a"), Some(Metainfo::Integer(1)));
+        assert_eq!(dictionary.remove("a"), None);
+        assert_eq!(dictionary.encode(), b"d1:bi2e1:ci4ee");
+    }
+
+    #[test]
+    fn sort_nested_keys() {
+        let mut dictionary = Dictionary::from([
+            (
+                "z",
+                Metainfo::List(vec![Metainfo::Dictionary(Dictionary::from([
+                    ("y", Metainfo::Integer(1)),
+                    ("x", Metainfo::Integer(2)),
+                ]))]),
+            ),
+            ("a", Metainfo::Integer(3)),
+        ]);
+        dictionary.sort_keys();
+
+        assert_eq!(dictionary.encode(), b"d1:ai3e1:zld1:xi2e1:yi1eeee");
+    }
+}
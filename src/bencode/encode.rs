@@ -6,17 +6,13 @@ pub trait Encode {
 
 impl Encode for String {
     fn encode(&self) -> Vec<u8> {
-        format!("{}:{}", self.chars().count(), self)
-            .as_bytes()
-            .to_vec()
+        format!("{}:{}", self.len(), self).as_bytes().to_vec()
     }
 }
 
 impl Encode for &str {
     fn encode(&self) -> Vec<u8> {
-        format!("{}:{}", self.chars().count(), self)
-            .as_bytes()
-            .to_vec()
+        format!("{}:{}", self.len(), self).as_bytes().to_vec()
     }
 }
 
@@ -121,7 +117,7 @@ mod test {
 
     #[test]
     fn encode_string_dict_entry() {
-        let output = encode_dict_entry(&"key".to_owned(), &"value".to_owned());
+        let output = encode_dict_entry("key", &"value".to_owned());
 
         let expected_output = "3:key5:value".as_bytes();
         assert_eq!(output, expected_output);
@@ -129,7 +125,7 @@ mod test {
 
     #[test]
     fn encode_integer_dict_entry() {
-        let output = encode_dict_entry(&"key".to_owned(), &123);
+        let output = encode_dict_entry("key", &123);
 
         let expected_output = "3:keyi123e".as_bytes();
         assert_eq!(output, expected_output);
@@ -137,7 +133,7 @@ mod test {
 
     #[test]
     fn encode_list_dict_entry() {
-        let output = encode_dict_entry(&"key".to_owned(), &Vec::from(["S".to_owned()]));
+        let output = encode_dict_entry("key", &Vec::from(["S".to_owned()]));
 
         let expected_output = "3:keyl1:Se".as_bytes();
         assert_eq!(output, expected_output);
@@ -145,7 +141,7 @@ mod test {
 
     #[test]
     fn encode_option_string_dict_entry() {
-        let output = encode_dict_entry(&"key".to_owned(), &Some("value".to_owned()));
+        let output = encode_dict_entry("key", &Some("value".to_owned()));
 
         let expected_output = "3:key5:value".as_bytes();
         assert_eq!(output, expected_output);
@@ -154,7 +150,7 @@ mod test {
     #[test]
     fn encode_option_none_dict_entry() {
         let none: Option<usize> = None;
-        let output = encode_dict_entry(&"key".to_owned(), &none);
+        let output = encode_dict_entry("key", &none);
 
         let expected_output: Vec<u8> = vec![];
         assert_eq!(output, expected_output);
@@ -200,4 +196,13 @@ mod test {
         let expected_output: Vec<u8> = vec![];
         assert_eq!(output, expected_output);
     }
+
+    #[test]
+    fn encode_non_ascii_string() {
+        let input = "città".to_owned();
+        let output = input.encode();
+
+        let expected_output = "6:città".as_bytes();
+        assert_eq!(output, expected_output);
+    }
 }
@@ -0,0 +1,31 @@
+use std::fmt::Display;
+
+use crate::bencode::decode::DecoderError;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum BencodeError {
+    #[error("{0}")]
+    Custom(String),
+    #[error("Type {0} cannot be represented in bencode")]
+    UnsupportedType(&'static str),
+    #[error("Dictionary keys must be strings")]
+    KeyMustBeAString(),
+    #[error("Nothing to decode")]
+    NoValue(),
+    #[error("Unexpected trailing bytes at position {0}")]
+    TrailingBytes(usize),
+    #[error("Error during decoding")]
+    Decoder(#[from] DecoderError),
+}
+
+impl serde::ser::Error for BencodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        BencodeError::Custom(msg.to_string())
+    }
+}
+
+impl serde::de::Error for BencodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        BencodeError::Custom(msg.to_string())
+    }
+}
@@ -0,0 +1,131 @@
+use serde_json::{Map, Number, Value};
+
+use crate::bencode::dictionary::Dictionary;
+use crate::bencode::metainfo::Metainfo;
+use crate::common::hex;
+
+// Strings that are not valid UTF-8, such as the pieces hashes, are written in JSON as an
+// object with this single key holding their hex encoding.
+pub const HEX_KEY: &str = "$hex";
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum JsonError {
+    #[error("Dictionary key {0} is not valid UTF-8")]
+    BinaryKey(String),
+    #[error("JSON {0} has no bencode equivalent")]
+    Unsupported(&'static str),
+    #[error("Number {0} is not an integer")]
+    NotAnInteger(Number),
+    #[error("Invalid hex string {0:?}")]
+    InvalidHex(String),
+}
+
+pub fn to_json(value: &Metainfo) -> Result<Value, JsonError> {
+    match value {
+        Metainfo::Integer(integer) => Ok(Value::from(*integer)),
+        Metainfo::String(bytes) => Ok(match std::str::from_utf8(bytes) {
+            Ok(string) => Value::from(string),
+            Err(_) => Value::Object(Map::from_iter([(
+                HEX_KEY.to_owned(),
+                Value::from(hex::encode(bytes)),
+            )])),
+        }),
+        Metainfo::List(values) => values.iter().map(to_json).collect(),
+        Metainfo::Dictionary(dictionary) => dictionary
+            .iter()
+            .map(|(key, value)| {
+                let key = String::from_utf8(key.to_vec())
+                    .map_err(|_| JsonError::BinaryKey(hex::encode(key)))?;
+                Ok((key, to_json(value)?))
+            })
+            .collect(),
+        Metainfo::Nothing() => Ok(Value::Null),
+    }
+}
+
+// Booleans become 0 or 1, as with the serializer. Dictionary keys keep the JSON order, see
+// Metainfo::sort_keys for the canonical one.
+pub fn from_json(value: &Value) -> Result<Metainfo, JsonError> {
+    match value {
+        Value::Null => Err(JsonError::Unsupported("null")),
+        Value::Bool(boolean) => Ok(Metainfo::Integer(*boolean as i64)),
+        Value::Number(number) => number
+            .as_i64()
+            .map(Metainfo::Integer)
+            .ok_or(JsonError::NotAnInteger(number.clone())),
+        Value::String(string) => Ok(Metainfo::String(string.as_bytes().to_vec())),
+        Value::Array(values) => Ok(Metainfo::List(
+            values.iter().map(from_json).collect::<Result<_, _>>()?,
+        )),
+        Value::Object(map) => match map.get(HEX_KEY) {
+            Some(Value::String(encoded)) if map.len() == 1 => hex::decode(encoded)
+                .map(Metainfo::String)
+                .ok_or(JsonError::InvalidHex(encoded.to_owned())),
+            _ => Ok(Metainfo::Dictionary(
+                map.iter()
+                    .map(|(key, value)| Ok((key.as_str(), from_json(value)?)))
+                    .collect::<Result<Dictionary, JsonError>>()?,
+            )),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn convert_to_json_and_back() {
+        let value = Metainfo::Dictionary(Dictionary::from([
+            ("announce", Metainfo::String(b"udp://tracker:1337".to_vec())),
+            (
+                "info",
+                Metainfo::Dictionary(Dictionary::from([
+                    ("length", Metainfo::Integer(-5)),
+                    ("pieces", Metainfo::String(vec![0x00, 0xff, 0x10])),
+                ])),
+            ),
+            ("list", Metainfo::List(vec![Metainfo::String(vec![])])),
+        ]));
+
+        let converted = to_json(&value).unwrap();
+        assert_eq!(
+            converted,
+            json!({
+                "announce": "udp://tracker:1337",
+                "info": {"length": -5, "pieces": {"$hex": "00ff10"}},
+                "list": [""]
+            })
+        );
+        assert_eq!(from_json(&converted), Ok(value));
+    }
+
+    #[test]
+    fn binary_key_to_json() {
+        let value =
+            Metainfo::Dictionary(Dictionary::from([(vec![0xfe, 0xff], Metainfo::Integer(1))]));
+
+        assert_eq!(
+            to_json(&value),
+            Err(JsonError::BinaryKey("feff".to_owned()))
+        );
+    }
+
+    #[test]
+    fn unsupported_json() {
+        assert_eq!(
+            from_json(&json!({"a": null})),
+            Err(JsonError::Unsupported("null"))
+        );
+        assert_eq!(
+            from_json(&json!([1.5])),
+            Err(JsonError::NotAnInteger(Number::from_f64(1.5).unwrap()))
+        );
+        assert_eq!(
+            from_json(&json!({"$hex": "zz"})),
+            Err(JsonError::InvalidHex("zz".to_owned()))
+        );
+        assert_eq!(from_json(&json!(true)), Ok(Metainfo::Integer(1)));
+    }
+}
@@ -1,13 +1,15 @@
-use std::collections::HashMap;
 use std::str::Utf8Error;
 use std::{fmt, str};
 
+use crate::bencode::dictionary::Dictionary;
+use crate::bencode::encode::Encode;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Metainfo {
-    Integer(usize),
+    Integer(i64),
     List(Vec<Metainfo>),
     String(Vec<u8>),
-    Dictionary(HashMap<String, Metainfo>),
+    Dictionary(Dictionary),
     Nothing(),
 }
 
@@ -30,97 +32,34 @@ impl fmt::Display for Metainfo {
 
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum MetainfoError {
-    #[error("Expected {0} type but found {1}")]
-    BadMetainfoMatch(&'static str, String),
     #[error("Utf8 string conversion failed")]
     Utf8ConversionError(#[from] Utf8Error),
     #[error("No key {0} found in dictionary")]
     NoKeyInDictionary(String),
 }
 
+// Values are walked by hand only to be printed or edited, types with a known layout derive
+// it through serde instead, see the de and ser modules.
 impl Metainfo {
-    fn get_bytes_content(&self) -> Result<Vec<u8>, MetainfoError> {
-        match &self {
-            Metainfo::String(value) => Ok(value.to_vec()),
-            _ => Err(MetainfoError::BadMetainfoMatch(
-                METAINFO_STRING,
-                self.to_string(),
-            )),
-        }
-    }
-
-    pub fn get_string_content(&self) -> Result<String, MetainfoError> {
-        match &self {
-            Metainfo::String(value) => Ok(str::from_utf8(value)?.to_string()),
-            _ => Err(MetainfoError::BadMetainfoMatch(
-                METAINFO_STRING,
-                self.to_string(),
-            )),
-        }
-    }
-
-    pub fn get_integer_content(&self) -> Result<usize, MetainfoError> {
-        match &self {
-            Metainfo::Integer(value) => Ok(*value),
-            _ => Err(MetainfoError::BadMetainfoMatch(
-                METAINFO_INTEGER,
-                self.to_string(),
-            )),
-        }
-    }
-
-    pub fn get_list_content(&self) -> Result<&Vec<Metainfo>, MetainfoError> {
-        match &self {
-            Metainfo::List(value) => Ok(value),
-            _ => Err(MetainfoError::BadMetainfoMatch(
-                METAINFO_LIST,
-                self.to_string(),
-            )),
-        }
-    }
-
-    pub fn get_dict_content(&self) -> Result<&HashMap<String, Metainfo>, MetainfoError> {
-        match &self {
-            Metainfo::Dictionary(value) => Ok(value),
-            _ => Err(MetainfoError::BadMetainfoMatch(
-                METAINFO_DICTIONARY,
-                self.to_string(),
-            )),
-        }
-    }
-
-    pub fn get_value_from_dict(&self, key: &str) -> Result<&Metainfo, MetainfoError> {
-        match self.get_dict_content()?.get(key) {
-            Some(value) => Ok(value),
-            _ => Err(MetainfoError::NoKeyInDictionary(key.to_string())),
-        }
-    }
-
-    pub fn get_bytes_from_dict(&self, key: &str) -> Result<Vec<u8>, MetainfoError> {
-        match self.get_dict_content()?.get(key) {
-            Some(value) => value.get_bytes_content(),
-            _ => Err(MetainfoError::NoKeyInDictionary(key.to_string())),
-        }
-    }
-
-    pub fn get_string_from_dict(&self, key: &str) -> Result<String, MetainfoError> {
-        match self.get_dict_content()?.get(key) {
-            Some(value) => value.get_string_content(),
-            _ => Err(MetainfoError::NoKeyInDictionary(key.to_string())),
-        }
-    }
-
-    pub fn get_integer_from_dict(&self, key: &str) -> Result<usize, MetainfoError> {
-        match self.get_dict_content()?.get(key) {
-            Some(value) => value.get_integer_content(),
-            _ => Err(MetainfoError::NoKeyInDictionary(key.to_string())),
+    // Keys of every nested dictionary are sorted, giving the canonical encoding.
+    pub fn sort_keys(&mut self) {
+        match self {
+            Metainfo::List(values) => values.iter_mut().for_each(Metainfo::sort_keys),
+            Metainfo::Dictionary(dictionary) => dictionary.sort_keys(),
+            _ => (),
         }
     }
+}
 
-    pub fn get_list_from_dict(&self, key: &str) -> Result<&Vec<Metainfo>, MetainfoError> {
-        match self.get_dict_content()?.get(key) {
-            Some(value) => value.get_list_content(),
-            _ => Err(MetainfoError::NoKeyInDictionary(key.to_string())),
+// Dictionaries are written in their own order, see sort_keys for the canonical one.
+impl Encode for Metainfo {
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Metainfo::Integer(value) => format!("i{}e", value).into_bytes(),
+            Metainfo::List(values) => values.encode(),
+            Metainfo::String(value) => value.encode(),
+            Metainfo::Dictionary(dictionary) => dictionary.encode(),
+            Metainfo::Nothing() => vec![],
         }
     }
 }
@@ -129,96 +68,27 @@ impl Metainfo {
 mod test {
 
     use super::*;
+    use crate::bencode::decode::Decoder;
 
     #[test]
-    fn get_bytes_content_test() {
-        let input = Metainfo::String(vec![b'C']);
-        let output = input.get_bytes_content();
-        assert_eq!(output, Ok(vec![b'C']));
-    }
-
-    #[test]
-    fn get_string_content_test() {
-        let input = Metainfo::String("Ciao".to_owned().as_bytes().to_vec());
-        let output = input.get_string_content();
-        let expected_output = Ok("Ciao".to_owned());
-        assert_eq!(output, expected_output);
-    }
-
-    #[test]
-    fn get_integer_content_test() {
-        let input = Metainfo::Integer(1234);
-        let output = input.get_integer_content();
-        let expected_output = Ok(1234);
-        assert_eq!(output, expected_output);
-    }
-
-    #[test]
-    fn get_list_content_test() {
-        let list_content = vec![
-            Metainfo::Integer(12),
-            Metainfo::String("ciao".as_bytes().to_vec()),
-        ];
-        let input = Metainfo::List(list_content.clone());
-        let output = input.get_list_content();
-        let expected_output = Ok(&list_content);
-        assert_eq!(output, expected_output);
-    }
-
-    #[test]
-    fn get_bytes_from_dict_test() {
-        let input = Metainfo::Dictionary(HashMap::from([(
-            "key".to_owned(),
-            Metainfo::String(vec![b'v', b'a', b'l', b'u', b'e']),
-        )]));
-        let output = input.get_bytes_from_dict(&"key".to_owned());
-
-        let expected_output = Ok(vec![b'v', b'a', b'l', b'u', b'e']);
-        assert_eq!(output, expected_output);
-    }
-
-    #[test]
-    fn get_string_from_dict_test() {
-        let input = Metainfo::Dictionary(HashMap::from([(
-            "key".to_owned(),
-            Metainfo::String("value".as_bytes().to_vec()),
-        )]));
-        let output = input.get_string_from_dict(&"key".to_owned());
-
-        let expected_output = Ok("value".to_owned());
-        assert_eq!(output, expected_output);
-    }
-
-    #[test]
-    fn get_list_from_dict_test() {
-        let input = Metainfo::Dictionary(HashMap::from([(
-            "key".to_owned(),
-            Metainfo::List(vec![Metainfo::Integer(123)]),
-        )]));
-        let output = input.get_list_from_dict(&"key".to_owned());
-
-        let vector = &vec![Metainfo::Integer(123)];
-        let expected_output = Ok(vector);
-        assert_eq!(output, expected_output);
-    }
-
-    #[test]
-    fn get_integer_from_dict_test() {
-        let input =
-            Metainfo::Dictionary(HashMap::from([("key".to_owned(), Metainfo::Integer(123))]));
-        let output = input.get_integer_from_dict(&"key".to_owned());
-
-        let expected_output = Ok(123);
-        assert_eq!(output, expected_output);
-    }
-
-    #[test]
-    fn get_value_from_dict_test() {
-        let input =
-            Metainfo::Dictionary(HashMap::from([("key".to_owned(), Metainfo::Integer(123))]));
-        let output = input.get_value_from_dict(&"key".to_owned());
-
-        let expected_output = Ok(&Metainfo::Integer(123));
-        assert_eq!(output, expected_output);
+    fn encode_decoded_value_unchanged() {
+        let bytes = [
+            "d4:zetai1e2:".as_bytes(),
+            &[0xfe, 0xff],
+            "1:a1:lld1:yi2e1:xi3eeee".as_bytes(),
+        ]
+        .concat();
+        let mut value = Decoder::init(bytes.to_vec()).decode().unwrap();
+
+        assert_eq!(value.encode(), bytes);
+
+        value.sort_keys();
+        let canonical = [
+            "d1:lld1:xi3e1:yi2eee4:zetai1e2:".as_bytes(),
+            &[0xfe, 0xff],
+            "1:ae".as_bytes(),
+        ]
+        .concat();
+        assert_eq!(value.encode(), canonical);
     }
 }
@@ -1,3 +1,11 @@
+pub mod bytes;
+pub mod de;
 pub mod decode;
+pub mod dictionary;
 pub mod encode;
+pub mod error;
+pub mod json;
 pub mod metainfo;
+pub mod ser;
+pub mod stream;
+pub mod view;
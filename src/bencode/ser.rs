@@ -0,0 +1,693 @@
+use serde::ser::{self, Serialize};
+
+use crate::bencode::error::BencodeError;
+
+// Name of the newtype struct whose bytes are written as they are, see Raw.
+const RAW_VALUE: &str = "$bencode::raw";
+
+// Serialize any value to bencode. Dictionary keys are sorted as required by BEP 3 and
+// None values are left out of dictionaries. Bencode has no null, so None is refused anywhere
+// else.
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, BencodeError> {
+    let mut serializer = Serializer::default();
+    value.serialize(&mut serializer)?;
+    Ok(serializer.output)
+}
+
+// Already encoded bencode, written without any change, e.g. to keep an info dictionary
+// exactly as it was received.
+pub struct Raw<'a>(pub &'a [u8]);
+
+impl Serialize for Raw<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(RAW_VALUE, &RawBytes(self.0))
+    }
+}
+
+struct RawBytes<'a>(&'a [u8]);
+
+impl Serialize for RawBytes<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+#[derive(Default)]
+pub struct Serializer {
+    output: Vec<u8>,
+    raw: bool,
+    // Set while writing the value of a dictionary entry, the entry is left out when nothing
+    // is written.
+    field: bool,
+}
+
+impl Serializer {
+    fn write_integer(&mut self, value: impl ToString) {
+        self.output.push(b'i');
+        self.output.extend_from_slice(value.to_string().as_bytes());
+        self.output.push(b'e');
+    }
+
+    fn write_bytes(&mut self, value: &[u8]) {
+        self.output
+            .extend_from_slice(value.len().to_string().as_bytes());
+        self.output.push(b':');
+        self.output.extend_from_slice(value);
+    }
+
+    fn write_nothing(&mut self, kind: &'static str) -> Result<(), BencodeError> {
+        match self.field {
+            true => Ok(()),
+            false => Err(BencodeError::UnsupportedType(kind)),
+        }
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = BencodeError;
+
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = SeqSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = MapSerializer<'a>;
+    type SerializeStructVariant = MapSerializer<'a>;
+
+    // Bencode has no booleans, they are written as 0 and 1.
+    fn serialize_bool(self, v: bool) -> Result<(), BencodeError> {
+        self.write_integer(v as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), BencodeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), BencodeError> {
+        self.write_integer(v);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), BencodeError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), BencodeError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), BencodeError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), BencodeError> {
+        self.write_integer(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), BencodeError> {
+        Err(BencodeError::UnsupportedType("f32"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), BencodeError> {
+        Err(BencodeError::UnsupportedType("f64"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), BencodeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), BencodeError> {
+        self.write_bytes(v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), BencodeError> {
+        match self.raw {
+            true => self.output.extend_from_slice(v),
+            false => self.write_bytes(v),
+        }
+        Ok(())
+    }
+
+    // Nothing is written, the enclosing dictionary skips the entry.
+    fn serialize_none(self) -> Result<(), BencodeError> {
+        self.write_nothing("None")
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), BencodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), BencodeError> {
+        self.write_nothing("()")
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), BencodeError> {
+        self.write_nothing(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), BencodeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), BencodeError> {
+        self.raw = name == RAW_VALUE;
+        let result = value.serialize(&mut *self);
+        self.raw = false;
+        result
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), BencodeError> {
+        self.field = false;
+        self.output.push(b'd');
+        self.write_bytes(variant.as_bytes());
+        value.serialize(&mut *self)?;
+        self.output.push(b'e');
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSerializer<'a>, BencodeError> {
+        self.field = false;
+        self.output.push(b'l');
+        Ok(SeqSerializer {
+            ser: self,
+            variant: false,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>, BencodeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, BencodeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SeqSerializer<'a>, BencodeError> {
+        self.field = false;
+        self.output.push(b'd');
+        self.write_bytes(variant.as_bytes());
+        self.output.push(b'l');
+        Ok(SeqSerializer {
+            ser: self,
+            variant: true,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a>, BencodeError> {
+        Ok(MapSerializer {
+            ser: self,
+            entries: vec![],
+            key: None,
+            variant: false,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer<'a>, BencodeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer<'a>, BencodeError> {
+        self.output.push(b'd');
+        self.write_bytes(variant.as_bytes());
+        Ok(MapSerializer {
+            ser: self,
+            entries: vec![],
+            key: None,
+            variant: true,
+        })
+    }
+}
+
+pub struct SeqSerializer<'a> {
+    ser: &'a mut Serializer,
+    // Enum variants are wrapped in a dictionary with the variant name as the only key.
+    variant: bool,
+}
+
+impl SeqSerializer<'_> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn close(self) -> Result<(), BencodeError> {
+        self.ser.output.push(b'e');
+        if self.variant {
+            self.ser.output.push(b'e');
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = BencodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), BencodeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = BencodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), BencodeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = BencodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), BencodeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = BencodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<(), BencodeError> {
+        self.close()
+    }
+}
+
+// Entries are collected and written once complete, since the keys have to be sorted by
+// their raw bytes.
+pub struct MapSerializer<'a> {
+    ser: &'a mut Serializer,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    key: Option<Vec<u8>>,
+    variant: bool,
+}
+
+impl MapSerializer<'_> {
+    fn push<T: Serialize + ?Sized>(&mut self, key: Vec<u8>, value: &T) -> Result<(), BencodeError> {
+        let mut serializer = Serializer {
+            field: true,
+            ..Serializer::default()
+        };
+        value.serialize(&mut serializer)?;
+        if !serializer.output.is_empty() {
+            self.entries.push((key, serializer.output));
+        }
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<(), BencodeError> {
+        self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        self.ser.output.push(b'd');
+        for (key, value) in self.entries {
+            self.ser.write_bytes(&key);
+            self.ser.output.extend_from_slice(&value);
+        }
+        self.ser.output.push(b'e');
+
+        if self.variant {
+            self.ser.output.push(b'e');
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for MapSerializer<'_> {
+    type Ok = ();
+    type Error = BencodeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), BencodeError> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BencodeError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| BencodeError::Custom("Value serialized before its key".to_owned()))?;
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<(), BencodeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer<'_> {
+    type Ok = ();
+    type Error = BencodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), BencodeError> {
+        self.push(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<(), BencodeError> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer<'_> {
+    type Ok = ();
+    type Error = BencodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), BencodeError> {
+        self.push(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<(), BencodeError> {
+        self.close()
+    }
+}
+
+// Dictionary keys are byte strings, anything else is refused.
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Vec<u8>;
+    type Error = BencodeError;
+
+    type SerializeSeq = ser::Impossible<Vec<u8>, BencodeError>;
+    type SerializeTuple = ser::Impossible<Vec<u8>, BencodeError>;
+    type SerializeTupleStruct = ser::Impossible<Vec<u8>, BencodeError>;
+    type SerializeTupleVariant = ser::Impossible<Vec<u8>, BencodeError>;
+    type SerializeMap = ser::Impossible<Vec<u8>, BencodeError>;
+    type SerializeStruct = ser::Impossible<Vec<u8>, BencodeError>;
+    type SerializeStructVariant = ser::Impossible<Vec<u8>, BencodeError>;
+
+    fn serialize_str(self, v: &str) -> Result<Vec<u8>, BencodeError> {
+        Ok(v.as_bytes().to_vec())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<u8>, BencodeError> {
+        Ok(v.to_vec())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Vec<u8>, BencodeError> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Vec<u8>, BencodeError> {
+        Ok(variant.as_bytes().to_vec())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Vec<u8>, BencodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_none(self) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_unit(self) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Vec<u8>, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, BencodeError> {
+        Err(BencodeError::KeyMustBeAString())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::bencode::bytes;
+
+    #[derive(Serialize)]
+    struct Sample {
+        name: String,
+        #[serde(rename = "piece length")]
+        piece_length: usize,
+        #[serde(with = "bytes")]
+        pieces: Vec<u8>,
+        comment: Option<String>,
+        tags: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    enum Event {
+        Started,
+        Completed(usize),
+    }
+
+    #[derive(Serialize)]
+    enum Wrapper {
+        Value(Option<u8>),
+    }
+
+    #[test]
+    fn serialize_struct_with_sorted_keys() {
+        let sample = Sample {
+            name: "a".to_owned(),
+            piece_length: 4,
+            pieces: vec![0xff, 0x00],
+            comment: None,
+            tags: vec!["x".to_owned()],
+        };
+
+        let expected = [
+            "d4:name1:a12:piece lengthi4e6:pieces2:".as_bytes(),
+            &[0xff, 0x00],
+            "4:tagsl1:xee".as_bytes(),
+        ]
+        .concat();
+        assert_eq!(to_bytes(&sample).unwrap(), expected);
+    }
+
+    #[test]
+    fn serialize_map_and_enum() {
+        let map = HashMap::from([("zz", 1), ("a", -2)]);
+        assert_eq!(to_bytes(&map).unwrap(), b"d1:ai-2e2:zzi1ee");
+
+        assert_eq!(to_bytes(&Event::Started).unwrap(), b"7:Started");
+        assert_eq!(
+            to_bytes(&vec![Event::Completed(3)]).unwrap(),
+            b"ld9:Completedi3eee"
+        );
+    }
+
+    #[test]
+    fn serialize_raw_value() {
+        let map = HashMap::from([("info", Raw(b"d1:xi1ee"))]);
+
+        assert_eq!(to_bytes(&map).unwrap(), b"d4:infod1:xi1eee");
+    }
+
+    #[test]
+    fn refuse_none_outside_dictionaries() {
+        let none = Err(BencodeError::UnsupportedType("None"));
+
+        assert_eq!(to_bytes(&None::<u8>), none);
+        assert_eq!(to_bytes(&vec![None, Some(1)]), none);
+        assert_eq!(to_bytes(&Wrapper::Value(None)), none);
+        assert_eq!(to_bytes(&()), Err(BencodeError::UnsupportedType("()")));
+        assert_eq!(
+            to_bytes(&HashMap::from([("a", Some(vec![None::<u8>]))])),
+            none
+        );
+        assert_eq!(
+            to_bytes(&HashMap::from([("a", None), ("b", Some(1))])).unwrap(),
+            b"d1:bi1ee"
+        );
+    }
+
+    #[test]
+    fn serialize_unsupported_values() {
+        assert_eq!(to_bytes(&1.5), Err(BencodeError::UnsupportedType("f64")));
+        assert_eq!(
+            to_bytes(&HashMap::from([(1, 2)])),
+            Err(BencodeError::KeyMustBeAString())
+        );
+    }
+}
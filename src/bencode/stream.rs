@@ -0,0 +1,245 @@
+use std::io::Read;
+
+use crate::bencode::decode::{DecoderError, DecoderOptions};
+use crate::bencode::view::{View, ViewDecoder};
+
+const READ_CHUNK_SIZE: usize = 16 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum StreamDecoderError {
+    #[error("Error reading bencode: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Decoder(#[from] DecoderError),
+}
+
+// Decode values from a reader as their bytes arrive. Bytes are read until a whole value is
+// buffered, the returned view borrows from the buffer until the next value is requested.
+pub struct StreamDecoder<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    consumed: usize,
+    options: DecoderOptions,
+    scanner: Scanner,
+}
+
+impl<R: Read> StreamDecoder<R> {
+    pub fn new(reader: R) -> StreamDecoder<R> {
+        StreamDecoder {
+            reader,
+            buffer: vec![],
+            consumed: 0,
+            options: DecoderOptions::default(),
+            scanner: Scanner::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: DecoderOptions) -> StreamDecoder<R> {
+        self.options = options;
+        self
+    }
+
+    // None is returned once the reader is over. Spans of the view are relative to the
+    // start of the value.
+    pub fn decode(&mut self) -> Result<Option<View<'_>>, StreamDecoderError> {
+        self.buffer.drain(..self.consumed);
+        self.consumed = 0;
+        self.scanner = Scanner::default();
+
+        let length = loop {
+            match self.scanner.scan(&self.buffer, self.options) {
+                Scan::Complete(length) => break length,
+                // The decoder tells what is wrong with the value.
+                Scan::Invalid => break self.buffer.len(),
+                Scan::Incomplete => (),
+            }
+
+            if self.fill_buffer()? == 0 {
+                match self.buffer.is_empty() {
+                    true => return Ok(None),
+                    false => break self.buffer.len(),
+                }
+            }
+        };
+
+        let mut decoder = ViewDecoder::new(&self.buffer[..length]).with_options(self.options);
+        let view = decoder.decode()?;
+        self.consumed = decoder.get_total_parsed_bytes();
+        Ok(view)
+    }
+
+    fn fill_buffer(&mut self) -> Result<usize, std::io::Error> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        let read = self.reader.read(&mut chunk)?;
+        self.buffer.extend_from_slice(&chunk[..read]);
+        Ok(read)
+    }
+}
+
+enum Scan {
+    Complete(usize),
+    Incomplete,
+    Invalid,
+}
+
+// Finds where the next value ends without decoding it. The scan goes on from where the
+// previous one stopped, so a value arriving in many chunks is gone through only once
+// instead of once for each chunk.
+#[derive(Default)]
+struct Scanner {
+    position: usize,
+    depth: usize,
+}
+
+impl Scanner {
+    fn scan(&mut self, buffer: &[u8], options: DecoderOptions) -> Scan {
+        loop {
+            let Some(&byte) = buffer.get(self.position) else {
+                return Scan::Incomplete;
+            };
+
+            let end = match byte {
+                b'i' => match find(buffer, self.position, b'e') {
+                    Some(end) => end + 1,
+                    None => return Scan::Incomplete,
+                },
+                b'0'..=b'9' => match find(buffer, self.position, b':') {
+                    Some(separator) => match string_end(buffer, self.position, separator) {
+                        Some(end) => end,
+                        None => return Scan::Invalid,
+                    },
+                    None => return Scan::Incomplete,
+                },
+                b'l' | b'd' => {
+                    self.depth += 1;
+                    self.position + 1
+                }
+                b'e' if self.depth > 0 => {
+                    self.depth -= 1;
+                    self.position + 1
+                }
+                _ => return Scan::Invalid,
+            };
+
+            if end > options.get_max_size() || self.depth > options.get_max_depth() {
+                return Scan::Invalid;
+            }
+            if end > buffer.len() {
+                return Scan::Incomplete;
+            }
+
+            self.position = end;
+            if self.depth == 0 {
+                return Scan::Complete(end);
+            }
+        }
+    }
+}
+
+fn find(buffer: &[u8], start: usize, byte: u8) -> Option<usize> {
+    buffer[start..]
+        .iter()
+        .position(|&found| found == byte)
+        .map(|offset| start + offset)
+}
+
+// End of a string whose length is written between start and the separator.
+fn string_end(buffer: &[u8], start: usize, separator: usize) -> Option<usize> {
+    let length: usize = std::str::from_utf8(&buffer[start..separator])
+        .ok()?
+        .parse()
+        .ok()?;
+    (separator + 1).checked_add(length)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bencode::view::ViewValue;
+
+    // Reader handing out its bytes a few at a time, as a slow connection would.
+    struct SlowReader {
+        bytes: Vec<u8>,
+        position: usize,
+    }
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let end = (self.position + 3).min(self.bytes.len());
+            let read = end - self.position;
+            buf[..read].copy_from_slice(&self.bytes[self.position..end]);
+            self.position = end;
+            Ok(read)
+        }
+    }
+
+    fn slow_reader(bytes: &str) -> SlowReader {
+        SlowReader {
+            bytes: bytes.as_bytes().to_vec(),
+            position: 0,
+        }
+    }
+
+    #[test]
+    fn decode_values_as_they_arrive() {
+        let mut decoder = StreamDecoder::new(slow_reader("d5:peers6:abcdefei42e"));
+
+        let first = decoder.decode().unwrap().unwrap();
+        assert_eq!(first.get_span(), 0..17);
+        assert_eq!(
+            first.get(b"peers").unwrap().clone().into_value(),
+            ViewValue::String(b"abcdef")
+        );
+
+        let second = decoder.decode().unwrap().unwrap();
+        assert_eq!(second.into_value(), ViewValue::Integer(42));
+
+        assert!(decoder.decode().unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_large_value_in_chunks() {
+        let items = 4 * 1024 * 1024 / 7;
+        let bytes = [
+            b"d5:itemsl".to_vec(),
+            b"5:abcde".repeat(items),
+            b"e6:pieces2097152:".to_vec(),
+            vec![0; 2 * 1024 * 1024],
+            b"e".to_vec(),
+        ]
+        .concat();
+        let mut decoder = StreamDecoder::new(std::io::Cursor::new(&bytes));
+
+        let value = decoder.decode().unwrap().unwrap();
+        assert_eq!(value.get_span(), 0..bytes.len());
+        match value.get(b"items").unwrap().clone().into_value() {
+            ViewValue::List(values) => assert_eq!(values.len(), items),
+            other => panic!("Unexpected value {:?}", other),
+        }
+        assert!(decoder.decode().unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_truncated_stream() {
+        let mut decoder = StreamDecoder::new(slow_reader("l4:miao"));
+
+        assert!(matches!(
+            decoder.decode(),
+            Err(StreamDecoderError::Decoder(DecoderError::UnexpectedEnd(
+                7,
+                _
+            )))
+        ));
+    }
+
+    #[test]
+    fn decode_malformed_stream() {
+        let options = DecoderOptions::default().max_size(8);
+        let mut decoder = StreamDecoder::new(slow_reader("5000:abc")).with_options(options);
+
+        assert!(matches!(
+            decoder.decode(),
+            Err(StreamDecoderError::Decoder(DecoderError::SizeLimit(0, 8)))
+        ));
+    }
+}
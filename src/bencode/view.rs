@@ -0,0 +1,348 @@
+use std::ops::Range;
+
+use crate::bencode::decode::{DecoderError, DecoderOptions};
+use crate::bencode::dictionary::Dictionary;
+use crate::bencode::metainfo::Metainfo;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViewValue<'a> {
+    Integer(i64),
+    String(&'a [u8]),
+    List(Vec<View<'a>>),
+    Dictionary(Vec<(View<'a>, View<'a>)>),
+}
+
+// Decoded value borrowing its strings from the input, together with the bytes it was
+// decoded from and their position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct View<'a> {
+    value: ViewValue<'a>,
+    start: usize,
+    raw: &'a [u8],
+}
+
+impl<'a> View<'a> {
+    pub fn into_value(self) -> ViewValue<'a> {
+        self.value
+    }
+
+    pub fn get_raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    // Offsets within the whole input given to the decoder.
+    pub fn get_span(&self) -> Range<usize> {
+        self.start..self.start + self.raw.len()
+    }
+
+    // Value stored at the given key, when this is a dictionary.
+    pub fn get(&self, key: &[u8]) -> Option<&View<'a>> {
+        match &self.value {
+            ViewValue::Dictionary(entries) => entries
+                .iter()
+                .find(|(k, _)| k.value == ViewValue::String(key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn to_metainfo(&self) -> Metainfo {
+        match &self.value {
+            ViewValue::Integer(value) => Metainfo::Integer(*value),
+            ViewValue::String(value) => Metainfo::String(value.to_vec()),
+            ViewValue::List(values) => {
+                Metainfo::List(values.iter().map(View::to_metainfo).collect())
+            }
+            ViewValue::Dictionary(entries) => Metainfo::Dictionary(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.get_string(), value.to_metainfo()))
+                    .collect::<Dictionary>(),
+            ),
+        }
+    }
+
+    // Dictionary keys are always strings.
+    fn get_string(&self) -> &'a [u8] {
+        match self.value {
+            ViewValue::String(value) => value,
+            _ => &[],
+        }
+    }
+}
+
+// Decoder over borrowed bytes, nothing is copied. The decoder never panics on malformed
+// input, every error tells where decoding stopped and what was expected there.
+pub struct ViewDecoder<'a> {
+    contents: &'a [u8],
+    current: usize,
+    options: DecoderOptions,
+    depth: usize,
+    value_start: usize,
+}
+
+impl<'a> ViewDecoder<'a> {
+    pub fn new(contents: &'a [u8]) -> ViewDecoder<'a> {
+        ViewDecoder {
+            contents,
+            current: 0,
+            options: DecoderOptions::default(),
+            depth: 0,
+            value_start: 0,
+        }
+    }
+
+    pub fn with_options(mut self, options: DecoderOptions) -> ViewDecoder<'a> {
+        self.options = options;
+        self
+    }
+
+    // Start decoding after the given number of bytes, spans are still relative to the
+    // beginning of the contents.
+    pub fn starting_at(mut self, position: usize) -> ViewDecoder<'a> {
+        self.current = position;
+        self
+    }
+
+    pub fn get_total_parsed_bytes(&self) -> usize {
+        self.current
+    }
+
+    // Decode the next value, None is returned once the input is over.
+    pub fn decode(&mut self) -> Result<Option<View<'a>>, DecoderError> {
+        if self.current >= self.contents.len() {
+            return Ok(None);
+        }
+
+        self.depth = 0;
+        self.value_start = self.current;
+        self.parse_value().map(Some)
+    }
+
+    fn advance(&mut self) -> usize {
+        self.current += 1;
+        self.current
+    }
+
+    fn peek(&self, expected: &'static str) -> Result<u8, DecoderError> {
+        self.contents
+            .get(self.current)
+            .copied()
+            .ok_or(DecoderError::UnexpectedEnd(self.current, expected))
+    }
+
+    fn expect(&mut self, byte: u8, expected: &'static str) -> Result<(), DecoderError> {
+        match self.peek(expected)? {
+            found if found == byte => {
+                self.advance();
+                Ok(())
+            }
+            found => Err(DecoderError::UnexpectedByte(self.current, found, expected)),
+        }
+    }
+
+    fn check_size(&self, end: usize) -> Result<(), DecoderError> {
+        let max_size = self.options.get_max_size();
+        match end - self.value_start > max_size {
+            true => Err(DecoderError::SizeLimit(self.value_start, max_size)),
+            false => Ok(()),
+        }
+    }
+
+    fn view(&self, value: ViewValue<'a>, start: usize) -> View<'a> {
+        View {
+            value,
+            start,
+            raw: &self.contents[start..self.current],
+        }
+    }
+
+    // Digits of an integer or of a string length, at least one is required.
+    fn parse_digits(&mut self) -> Result<&'a str, DecoderError> {
+        let start = self.current;
+        while self
+            .contents
+            .get(self.current)
+            .is_some_and(|byte| byte.is_ascii_digit())
+        {
+            self.advance();
+        }
+
+        if start == self.current {
+            let found = self.peek("a digit")?;
+            return Err(DecoderError::UnexpectedByte(self.current, found, "a digit"));
+        }
+
+        let digits = &self.contents[start..self.current];
+        if self.options.is_strict() && digits.len() > 1 && digits[0] == b'0' {
+            return Err(DecoderError::InvalidInteger(start, "leading zero"));
+        }
+        // Only ASCII digits were taken, the conversion cannot fail.
+        Ok(std::str::from_utf8(digits).unwrap_or_default())
+    }
+
+    fn parse_integer(&mut self) -> Result<View<'a>, DecoderError> {
+        let start = self.current;
+        self.advance();
+
+        let negative = self.contents.get(self.current) == Some(&b'-');
+        if negative {
+            self.advance();
+        }
+
+        let digits = self.parse_digits()?;
+        if self.options.is_strict() && negative && digits == "0" {
+            return Err(DecoderError::InvalidInteger(start, "negative zero"));
+        }
+
+        let integer: i64 = match negative {
+            true => format!("-{digits}").parse(),
+            false => digits.parse(),
+        }
+        .map_err(|_| DecoderError::InvalidInteger(start, "out of range"))?;
+
+        self.expect(b'e', "end of integer")?;
+        Ok(self.view(ViewValue::Integer(integer), start))
+    }
+
+    fn parse_string(&mut self) -> Result<View<'a>, DecoderError> {
+        let start = self.current;
+        let length: usize = self
+            .parse_digits()?
+            .parse()
+            .map_err(|_| DecoderError::InvalidInteger(start, "out of range"))?;
+        self.expect(b':', "string length separator")?;
+
+        let end = self.current.saturating_add(length);
+        self.check_size(end)?;
+        if end > self.contents.len() {
+            return Err(DecoderError::UnexpectedEnd(
+                self.contents.len(),
+                "string content",
+            ));
+        }
+
+        let b = &self.contents[self.current..end];
+        self.current = end;
+        Ok(self.view(ViewValue::String(b), start))
+    }
+
+    fn enter(&mut self) -> Result<(), DecoderError> {
+        self.depth += 1;
+        let max_depth = self.options.get_max_depth();
+        match self.depth > max_depth {
+            true => Err(DecoderError::DepthLimit(self.current, max_depth)),
+            false => Ok(()),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<View<'a>, DecoderError> {
+        let start = self.current;
+        self.enter()?;
+        self.advance();
+        let mut list = Vec::new();
+
+        while self.peek("a list item or its end")? != b'e' {
+            list.push(self.parse_value()?);
+            self.check_size(self.current)?;
+        }
+
+        self.advance();
+        self.depth -= 1;
+        Ok(self.view(ViewValue::List(list), start))
+    }
+
+    fn parse_dictionary(&mut self) -> Result<View<'a>, DecoderError> {
+        let start = self.current;
+        self.enter()?;
+        self.advance();
+
+        let mut entries: Vec<(View<'a>, View<'a>)> = Vec::new();
+        while self.peek("a dictionary key or its end")? != b'e' {
+            let key_start = self.current;
+            let key = self.parse_string()?;
+
+            let sorted = match entries.last() {
+                Some((last, _)) => last.get_string() < key.get_string(),
+                None => true,
+            };
+            if self.options.is_strict() && !sorted {
+                return Err(DecoderError::UnsortedKey(key_start));
+            }
+
+            let value = self.parse_value()?;
+            self.check_size(self.current)?;
+            entries.push((key, value));
+        }
+
+        self.advance();
+        self.depth -= 1;
+        Ok(self.view(ViewValue::Dictionary(entries), start))
+    }
+
+    fn parse_value(&mut self) -> Result<View<'a>, DecoderError> {
+        match self.peek("a value")? {
+            b'i' => self.parse_integer(),
+            b'l' => self.parse_list(),
+            b'd' => self.parse_dictionary(),
+            b'0'..=b'9' => self.parse_string(),
+            found => Err(DecoderError::UnexpectedByte(self.current, found, "a value")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn view_borrows_input_with_spans() {
+        let bytes = "d4:infod6:pieces4:abcde3:numi-3ee".as_bytes();
+        let view = ViewDecoder::new(bytes).decode().unwrap().unwrap();
+
+        assert_eq!(view.get_span(), 0..bytes.len());
+
+        let info = view.get(b"info").unwrap();
+        assert_eq!(info.get_span(), 7..23);
+        assert_eq!(info.get_raw(), b"d6:pieces4:abcde");
+
+        let pieces = info.get(b"pieces").unwrap();
+        assert_eq!(pieces.get_span(), 16..22);
+        assert!(std::ptr::eq(pieces.get_raw(), &bytes[16..22]));
+        assert_eq!(pieces.clone().into_value(), ViewValue::String(b"abcd"));
+
+        assert_eq!(
+            view.get(b"num").unwrap().clone().into_value(),
+            ViewValue::Integer(-3)
+        );
+        assert_eq!(view.get(b"missing"), None);
+    }
+
+    #[test]
+    fn view_to_metainfo() {
+        let bytes = "d1:bli1e2:xye1:a0:e".as_bytes();
+        let view = ViewDecoder::new(bytes).decode().unwrap().unwrap();
+
+        assert_eq!(
+            view.to_metainfo(),
+            Metainfo::Dictionary(Dictionary::from([
+                (
+                    "b",
+                    Metainfo::List(vec![Metainfo::Integer(1), Metainfo::String(b"xy".to_vec())])
+                ),
+                ("a", Metainfo::String(vec![])),
+            ]))
+        );
+    }
+
+    #[test]
+    fn decode_values_one_after_the_other() {
+        let bytes = "i1e3:abc".as_bytes();
+        let mut decoder = ViewDecoder::new(bytes);
+
+        assert_eq!(decoder.decode().unwrap().unwrap().get_span(), 0..3);
+        assert_eq!(decoder.decode().unwrap().unwrap().get_span(), 3..8);
+        assert_eq!(decoder.decode(), Ok(None));
+        assert_eq!(decoder.get_total_parsed_bytes(), 8);
+    }
+}
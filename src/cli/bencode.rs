@@ -0,0 +1,409 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Subcommand};
+
+use crate::bencode::decode::{Decoder, DecoderError};
+use crate::bencode::dictionary::Dictionary;
+use crate::bencode::encode::Encode;
+use crate::bencode::json::{from_json, to_json, JsonError};
+use crate::bencode::metainfo::Metainfo;
+use crate::bencode::view::ViewDecoder;
+use crate::common::hex;
+use crate::torrent::metafile::Metafile;
+
+// Binary strings longer than this are cut in the dump, unless asked otherwise.
+const DUMP_BINARY_BYTES: usize = 32;
+const DUMP_INDENT: &str = "  ";
+
+#[derive(Args, Debug)]
+pub struct BencodeArgs {
+    #[command(subcommand)]
+    pub command: BencodeCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BencodeCommand {
+    /// Print every value of a bencoded file as an indented tree
+    Dump {
+        file: PathBuf,
+
+        /// Refuse encodings that are not canonical, e.g. unsorted keys or leading zeros
+        #[arg(long)]
+        strict: bool,
+
+        /// Print binary strings in full instead of their first bytes
+        #[arg(long)]
+        full: bool,
+    },
+    /// Convert a bencoded file to JSON, binary strings become {"$hex": "..."}
+    ToJson {
+        file: PathBuf,
+
+        /// Where the JSON is written, stdout by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Convert a JSON file to bencode, with the dictionary keys sorted
+    FromJson {
+        file: PathBuf,
+
+        /// Where the bencode is written, stdout by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Inspect or edit a .torrent file
+    #[command(subcommand)]
+    Torrent(TorrentCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TorrentCommand {
+    /// Print name, files, pieces, trackers and info hash
+    Show { file: PathBuf },
+    /// Set a top level key, e.g. announce. The info dictionary is left untouched
+    Set {
+        file: PathBuf,
+        key: String,
+        value: String,
+
+        /// Parse the value as JSON, to set integers, lists or dictionaries
+        #[arg(long)]
+        json: bool,
+
+        /// Where the torrent is written, the file is edited in place by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Remove a top level key, e.g. announce-list. The info dictionary is left untouched
+    Remove {
+        file: PathBuf,
+        key: String,
+
+        /// Where the torrent is written, the file is edited in place by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum InspectError {
+    #[error("Cannot read {0}: {1}")]
+    Read(String, String),
+    #[error("Cannot write {0}: {1}")]
+    Write(String, String),
+    #[error("Invalid bencode: {0}")]
+    Decoder(#[from] DecoderError),
+    #[error("Invalid bencode: unexpected bytes after position {0}")]
+    TrailingBytes(usize),
+    #[error("Invalid JSON: {0}")]
+    Json(String),
+    #[error(transparent)]
+    Conversion(#[from] JsonError),
+    #[error("Invalid torrent file: {0}")]
+    Torrent(String),
+    #[error("No key {0} found in the torrent")]
+    NoKey(String),
+    #[error("The info dictionary cannot be edited, the info hash would change")]
+    InfoKey(),
+}
+
+pub fn run(args: BencodeArgs) -> Result<(), InspectError> {
+    match args.command {
+        BencodeCommand::Dump { file, strict, full } => {
+            let mut decoder = Decoder::init(read(&file)?);
+            if strict {
+                decoder = decoder.strict();
+            }
+
+            loop {
+                match decoder.decode()? {
+                    Metainfo::Nothing() => return Ok(()),
+                    value => print!("{}", dump(&value, full)),
+                }
+            }
+        }
+        BencodeCommand::ToJson { file, output } => {
+            let json = to_json(&decode_single(read(&file)?)?)?;
+            let pretty = serde_json::to_string_pretty(&json)
+                .map_err(|err| InspectError::Json(err.to_string()))?;
+            write(output.as_deref(), format!("{pretty}\n").as_bytes())
+        }
+        BencodeCommand::FromJson { file, output } => {
+            let json: serde_json::Value = serde_json::from_slice(&read(&file)?)
+                .map_err(|err| InspectError::Json(err.to_string()))?;
+            let mut value = from_json(&json)?;
+            value.sort_keys();
+            write(output.as_deref(), &value.encode())
+        }
+        BencodeCommand::Torrent(TorrentCommand::Show { file }) => {
+            let metafile = Metafile::from_bytes(read(&file)?)
+                .map_err(|err| InspectError::Torrent(err.to_string()))?;
+            print!("{}", show(&metafile)?);
+            Ok(())
+        }
+        BencodeCommand::Torrent(TorrentCommand::Set {
+            file,
+            key,
+            value,
+            json,
+            output,
+        }) => {
+            let value = match json {
+                true => from_json(
+                    &serde_json::from_str(&value)
+                        .map_err(|err| InspectError::Json(err.to_string()))?,
+                )?,
+                false => Metainfo::String(value.into_bytes()),
+            };
+            let edited = edit_torrent(&read(&file)?, &key, Some(value))?;
+            write(Some(output.as_deref().unwrap_or(&file)), &edited)
+        }
+        BencodeCommand::Torrent(TorrentCommand::Remove { file, key, output }) => {
+            let edited = edit_torrent(&read(&file)?, &key, None)?;
+            write(Some(output.as_deref().unwrap_or(&file)), &edited)
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, InspectError> {
+    std::fs::read(path)
+        .map_err(|err| InspectError::Read(path.display().to_string(), err.to_string()))
+}
+
+// Write to the given file, or to stdout when there is none.
+fn write(path: Option<&Path>, bytes: &[u8]) -> Result<(), InspectError> {
+    match path {
+        Some(path) => std::fs::write(path, bytes)
+            .map_err(|err| InspectError::Write(path.display().to_string(), err.to_string())),
+        None => std::io::stdout()
+            .write_all(bytes)
+            .map_err(|err| InspectError::Write("stdout".to_owned(), err.to_string())),
+    }
+}
+
+fn decode_single(bytes: Vec<u8>) -> Result<Metainfo, InspectError> {
+    let mut decoder = Decoder::init(bytes);
+    let value = decoder.decode()?;
+    let parsed_bytes = decoder.get_total_parsed_bytes();
+
+    match decoder.decode()? {
+        Metainfo::Nothing() => Ok(value),
+        _ => Err(InspectError::TrailingBytes(parsed_bytes)),
+    }
+}
+
+fn dump(value: &Metainfo, full: bool) -> String {
+    let mut out = String::new();
+    dump_value(value, 0, full, &mut out);
+    out
+}
+
+// The value is written from the current position of the line, nested values one
+// indentation level deeper.
+fn dump_value(value: &Metainfo, depth: usize, full: bool, out: &mut String) {
+    let indent = DUMP_INDENT.repeat(depth + 1);
+    match value {
+        Metainfo::Integer(integer) => out.push_str(&format!("{integer}\n")),
+        Metainfo::String(bytes) => out.push_str(&format!("{}\n", dump_string(bytes, full))),
+        Metainfo::List(values) if values.is_empty() => out.push_str("[]\n"),
+        Metainfo::List(values) => {
+            out.push_str("[\n");
+            for value in values {
+                out.push_str(&indent);
+                dump_value(value, depth + 1, full, out);
+            }
+            out.push_str(&format!("{}]\n", DUMP_INDENT.repeat(depth)));
+        }
+        Metainfo::Dictionary(dictionary) if dictionary.iter().next().is_none() => {
+            out.push_str("{}\n")
+        }
+        Metainfo::Dictionary(dictionary) => {
+            out.push_str("{\n");
+            for (key, value) in dictionary.iter() {
+                out.push_str(&format!("{indent}{}: ", dump_string(key, full)));
+                dump_value(value, depth + 1, full, out);
+            }
+            out.push_str(&format!("{}}}\n", DUMP_INDENT.repeat(depth)));
+        }
+        Metainfo::Nothing() => (),
+    }
+}
+
+// Text is quoted and escaped, anything that is not UTF-8 is shown as hex with its length.
+fn dump_string(bytes: &[u8], full: bool) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => format!("{text:?}"),
+        Err(_) if full || bytes.len() <= DUMP_BINARY_BYTES => {
+            format!("<{} bytes> {}", bytes.len(), hex::encode(bytes))
+        }
+        Err(_) => format!(
+            "<{} bytes> {}...",
+            bytes.len(),
+            hex::encode(&bytes[..DUMP_BINARY_BYTES])
+        ),
+    }
+}
+
+fn show(metafile: &Metafile) -> Result<String, InspectError> {
+    let info = metafile.get_info();
+    let files = info
+        .get_files()
+        .map_err(|err| InspectError::Torrent(err.to_string()))?;
+
+    let mut out = format!(
+        "Name:         {}\nInfo hash:    {}\nTotal length: {} bytes\nPiece length: {} bytes\nPieces:       {}\nPrivate:      {}\n",
+        info.get_name(),
+        hex::encode(&metafile.get_info_hash()),
+        info.get_total_length(),
+        info.get_piece_length(),
+        info.get_piece_count(),
+        if info.is_private() { "yes" } else { "no" },
+    );
+
+    out.push_str("Trackers:\n");
+    for tracker in metafile.get_trackers() {
+        out.push_str(&format!("{DUMP_INDENT}{tracker}\n"));
+    }
+
+    out.push_str("Files:\n");
+    for file in files {
+        out.push_str(&format!(
+            "{DUMP_INDENT}{} ({} bytes)\n",
+            file.get_path().join("/"),
+            file.get_length()
+        ));
+    }
+
+    Ok(out)
+}
+
+// Set, or remove when no value is given, a top level key of the torrent. The info
+// dictionary is copied as it was read, so that the info hash cannot change.
+fn edit_torrent(bytes: &[u8], key: &str, value: Option<Metainfo>) -> Result<Vec<u8>, InspectError> {
+    if key == "info" {
+        return Err(InspectError::InfoKey());
+    }
+
+    let view = ViewDecoder::new(bytes)
+        .decode()?
+        .ok_or(InspectError::Torrent("empty file".to_owned()))?;
+    let raw_info = view
+        .get(b"info")
+        .map(|info| info.get_raw())
+        .ok_or(InspectError::Torrent("no info dictionary".to_owned()))?;
+
+    let mut torrent: Dictionary = match view.to_metainfo() {
+        Metainfo::Dictionary(dictionary) => dictionary,
+        _ => return Err(InspectError::Torrent("not a dictionary".to_owned())),
+    };
+    match value {
+        Some(value) => {
+            torrent.insert_sorted(key, value);
+        }
+        None => {
+            torrent
+                .remove(key)
+                .ok_or(InspectError::NoKey(key.to_owned()))?;
+        }
+    }
+
+    let mut acc = vec![b'd'];
+    for (key, value) in torrent.iter() {
+        acc.append(&mut key.to_vec().encode());
+        match key {
+            b"info" => acc.extend_from_slice(raw_info),
+            _ => acc.append(&mut value.encode()),
+        }
+    }
+    acc.push(b'e');
+    Ok(acc)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TORRENT: &str =
+        "d8:announce12:udp://old:804:infod6:lengthi03e4:name1:a12:piece lengthi4e6:pieces0:ee";
+
+    #[test]
+    fn dump_tree() {
+        let value = Decoder::init(
+            [
+                "d4:listli1e0:lee4:name3:abc6:pieces3:".as_bytes(),
+                &[0x00, 0xff, 0x10],
+                "e".as_bytes(),
+            ]
+            .concat(),
+        )
+        .decode()
+        .unwrap();
+
+        assert_eq!(
+            dump(&value, false),
+            "{\n  \"list\": [\n    1\n    \"\"\n    []\n  ]\n  \"name\": \"abc\"\n  \"pieces\": <3 bytes> 00ff10\n}\n"
+        );
+    }
+
+    #[test]
+    fn dump_long_binary_string() {
+        let bytes = vec![0xff; DUMP_BINARY_BYTES + 1];
+
+        assert_eq!(
+            dump_string(&bytes, false),
+            format!("<33 bytes> {}...", "ff".repeat(DUMP_BINARY_BYTES))
+        );
+        assert_eq!(
+            dump_string(&bytes, true),
+            format!("<33 bytes> {}", "ff".repeat(DUMP_BINARY_BYTES + 1))
+        );
+    }
+
+    #[test]
+    fn edit_keeps_info_unchanged() {
+        let edited = edit_torrent(
+            TORRENT.as_bytes(),
+            "announce",
+            Some(Metainfo::String(b"udp://new:80".to_vec())),
+        )
+        .unwrap();
+        assert_eq!(
+            edited,
+            "d8:announce12:udp://new:804:infod6:lengthi03e4:name1:a12:piece lengthi4e6:pieces0:ee"
+                .as_bytes()
+        );
+
+        let edited = edit_torrent(&edited, "comment", Some(Metainfo::Integer(1))).unwrap();
+        let edited = edit_torrent(&edited, "announce", None).unwrap();
+        assert_eq!(
+            edited,
+            "d7:commenti1e4:infod6:lengthi03e4:name1:a12:piece lengthi4e6:pieces0:ee".as_bytes()
+        );
+    }
+
+    #[test]
+    fn edit_invalid_keys() {
+        assert_eq!(
+            edit_torrent(TORRENT.as_bytes(), "info", None),
+            Err(InspectError::InfoKey())
+        );
+        assert_eq!(
+            edit_torrent(TORRENT.as_bytes(), "comment", None),
+            Err(InspectError::NoKey("comment".to_owned()))
+        );
+        assert_eq!(
+            edit_torrent(b"li1ee", "comment", None),
+            Err(InspectError::Torrent("no info dictionary".to_owned()))
+        );
+    }
+
+    #[test]
+    fn decode_single_value() {
+        assert_eq!(decode_single(b"i1e".to_vec()), Ok(Metainfo::Integer(1)));
+        assert_eq!(
+            decode_single(b"i1ei2e".to_vec()),
+            Err(InspectError::TrailingBytes(3))
+        );
+    }
+}
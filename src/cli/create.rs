@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::bencode::encode::Encode;
+use crate::common::hex;
+use crate::torrent::builder::{BuilderError, TorrentBuilder};
+
+#[derive(Args, Debug)]
+pub struct CreateArgs {
+    /// File or directory to share
+    pub path: PathBuf,
+
+    /// Where the .torrent file is written, <name>.torrent in the current directory by default
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Tracker url, can be repeated. Comma separated urls form a single tier
+    #[arg(short, long)]
+    pub announce: Vec<String>,
+
+    /// Piece length in bytes, a power of two between 16 KiB and 16 MiB, chosen from the
+    /// total length of the files when omitted
+    #[arg(long)]
+    pub piece_length: Option<usize>,
+
+    /// Free text comment stored in the torrent
+    #[arg(long)]
+    pub comment: Option<String>,
+
+    /// Mark the torrent as private, peers are then only found through its trackers
+    #[arg(long)]
+    pub private: bool,
+
+    /// Leave the creation date out, so that the same files always give the same torrent
+    #[arg(long)]
+    pub no_creation_date: bool,
+}
+
+// Write the .torrent file and print its info hash and magnet link on stdout.
+pub fn run(args: CreateArgs) -> Result<(), BuilderError> {
+    let mut builder = TorrentBuilder::new(&args.path).private(args.private);
+
+    for tier in args.announce.iter() {
+        let trackers = tier.split(',').map(|url| url.trim().to_owned());
+        builder = builder.announce_tier(trackers.filter(|url| !url.is_empty()).collect());
+    }
+    if let Some(piece_length) = args.piece_length {
+        builder = builder.piece_length(piece_length);
+    }
+    if let Some(comment) = args.comment {
+        builder = builder.comment(comment);
+    }
+    if !args.no_creation_date {
+        builder = builder.creation_date(chrono::Utc::now().timestamp() as usize);
+    }
+
+    let metafile = builder.build()?;
+
+    let output = args
+        .output
+        .unwrap_or_else(|| PathBuf::from(format!("{}.torrent", metafile.get_info().get_name())));
+    std::fs::write(&output, metafile.encode())
+        .map_err(|err| BuilderError::Io(output.display().to_string(), err))?;
+
+    eprintln!("Torrent written to {}", output.display());
+    println!("{}", hex::encode(&metafile.get_info_hash()));
+    println!("{}", metafile.get_magnet_link());
+
+    Ok(())
+}
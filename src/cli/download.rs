@@ -0,0 +1,199 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use actix::prelude::*;
+use clap::Args;
+use url::Url;
+
+use crate::actors::events::EventBus;
+use crate::actors::messages::{GetTorrentStatus, PeerFound, TorrentRegistered};
+use crate::actors::torrent::{TorrentActor, TorrentState, TorrentStatus};
+use crate::actors::trackers_interface::TrackersInterfaceActor;
+use crate::config::Config;
+use crate::torrent::magnet;
+use crate::torrent::metafile::Metafile;
+use crate::tracker::tcp_tracker::HttpClient;
+
+const PROGRESS_BAR_WIDTH: usize = 40;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// A single torrent is downloaded, there is no reason to wait for more peers to start.
+const MIN_PEERS: usize = 1;
+
+struct StartedTorrent {
+    info_hash: Vec<u8>,
+    addr: Addr<TorrentActor>,
+    tracker_tiers: Vec<Vec<Url>>,
+    private: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DownloadArgs {
+    /// Magnet link or path to a .torrent file
+    pub source: String,
+
+    /// Directory where the downloaded files are written
+    #[arg(short, long)]
+    pub out: Option<PathBuf>,
+
+    /// Give up when the download is not completed within this many seconds
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// Give up when no peer gave the metadata of a magnet within this many seconds
+    #[arg(long, default_value_t = 300)]
+    pub metadata_timeout: u64,
+
+    /// Give up when no piece is written for this many seconds
+    #[arg(long, default_value_t = 600)]
+    pub stall_timeout: u64,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum DownloadError {
+    #[error("Invalid magnet: {0}")]
+    Magnet(String),
+    #[error("Invalid torrent file {0}: {1}")]
+    Torrent(String, String),
+    #[error("Download not completed within {0} seconds")]
+    Timeout(u64),
+    #[error("Metadata could not be fetched from any peer")]
+    Metadata(),
+    #[error("No progress for {0} seconds")]
+    Stalled(u64),
+    #[error("Download interrupted")]
+    Interrupted(),
+}
+
+// Run the same actors used by the server for a single torrent and wait until every
+// piece has been verified and written to disk.
+pub async fn run(args: DownloadArgs, mut config: Config) -> Result<(), DownloadError> {
+    if let Some(out) = args.out {
+        config.storage.output_dir = out;
+    }
+
+    let events = EventBus::new();
+
+    let trackers_interface =
+        TrackersInterfaceActor::new(&config.trackers.default, config.peers.port)
+            .http_client(HttpClient::new(&config.trackers))
+            .start();
+
+    let StartedTorrent {
+        info_hash,
+        addr: torrent,
+        tracker_tiers,
+        private,
+    } = start_torrent(&args.source, &config, &events, &trackers_interface)?;
+    trackers_interface.do_send(TorrentRegistered {
+        info_hash,
+        torrent_actor_addr: torrent.clone(),
+        tracker_tiers,
+        private,
+    });
+
+    // The status is polled, events of the bus may be dropped when the download is fast.
+    // A download without trackers or peers that never answer makes no progress, so it is
+    // given up after a while even without --timeout.
+    let metadata_deadline = Instant::now() + Duration::from_secs(args.metadata_timeout);
+    let stall_timeout = Duration::from_secs(args.stall_timeout);
+    let wait_completion = async {
+        let mut progress = (TorrentState::FetchingMetadata, 0);
+        let mut last_progress = Instant::now();
+        loop {
+            let status = torrent
+                .send(GetTorrentStatus)
+                .await
+                .map_err(|_| DownloadError::Interrupted())?;
+            if status.piece_count.is_some() {
+                render_progress(&status);
+            }
+
+            if progress != (status.state.clone(), status.pieces_completed) {
+                progress = (status.state.clone(), status.pieces_completed);
+                last_progress = Instant::now();
+            } else if last_progress.elapsed() > stall_timeout {
+                return Err(DownloadError::Stalled(args.stall_timeout));
+            }
+
+            match status.state {
+                TorrentState::Completed => return Ok(()),
+                TorrentState::MetadataFailed => return Err(DownloadError::Metadata()),
+                TorrentState::FetchingMetadata if Instant::now() > metadata_deadline => {
+                    return Err(DownloadError::Metadata())
+                }
+                _ => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+    };
+
+    let outcome = match args.timeout {
+        Some(seconds) => tokio::time::timeout(Duration::from_secs(seconds), wait_completion)
+            .await
+            .unwrap_or(Err(DownloadError::Timeout(seconds))),
+        None => wait_completion.await,
+    };
+
+    eprintln!();
+    outcome
+}
+
+fn start_torrent(
+    source: &str,
+    config: &Config,
+    events: &EventBus,
+    trackers_interface: &Addr<TrackersInterfaceActor>,
+) -> Result<StartedTorrent, DownloadError> {
+    if source.starts_with("magnet:") {
+        let magnet = magnet::parse_magnet(source.as_bytes().to_vec())
+            .map_err(|err| DownloadError::Magnet(err.to_string()))?;
+        let info_hash = magnet.get_info_hash();
+        let addr = TorrentActor::with_magnet(&magnet, config, events.clone())
+            .min_peers(MIN_PEERS)
+            .trackers_interface(trackers_interface.clone().recipient())
+            .start();
+        for peer in magnet.get_peers() {
+            addr.do_send(PeerFound { peer });
+        }
+
+        return Ok(StartedTorrent {
+            info_hash,
+            addr,
+            tracker_tiers: magnet.get_tracker_tiers(),
+            private: false,
+        });
+    }
+
+    let bytes = std::fs::read(source)
+        .map_err(|err| DownloadError::Torrent(source.to_owned(), err.to_string()))?;
+    let metafile = Metafile::from_bytes(bytes)
+        .map_err(|err| DownloadError::Torrent(source.to_owned(), err.to_string()))?;
+
+    let info_hash = metafile.get_info_hash();
+    let addr = TorrentActor::with_metafile(&metafile, config, events.clone())
+        .min_peers(MIN_PEERS)
+        .trackers_interface(trackers_interface.clone().recipient())
+        .start();
+
+    Ok(StartedTorrent {
+        info_hash,
+        addr,
+        tracker_tiers: metafile.get_tracker_tiers(),
+        private: metafile.get_info().is_private(),
+    })
+}
+
+fn render_progress(status: &TorrentStatus) {
+    let piece_count = status.piece_count.unwrap_or(0).max(1);
+    let filled = PROGRESS_BAR_WIDTH * status.pieces_completed.min(piece_count) / piece_count;
+
+    eprint!(
+        "\r{} [{}{}] {}/{} pieces",
+        status.name.as_deref().unwrap_or(&status.info_hash),
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        status.pieces_completed,
+        piece_count
+    );
+    let _ = std::io::stderr().flush();
+}
@@ -0,0 +1,205 @@
+pub mod bencode;
+pub mod create;
+pub mod download;
+
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::config::{Config, ConfigError};
+
+#[derive(Parser, Debug)]
+#[command(name = "rust_bit", version, about = "Toy BitTorrent client")]
+pub struct Cli {
+    /// Configuration file, TOML or JSON (.json extension)
+    #[arg(short, long, global = true)]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub overrides: Overrides,
+
+    /// Start the HTTP server when no command is given
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Download a single torrent without starting the HTTP server
+    Download(download::DownloadArgs),
+    /// Create a .torrent file from a local file or directory
+    Create(create::CreateArgs),
+    /// Inspect, convert and edit bencoded files such as .torrent files
+    Bencode(bencode::BencodeArgs),
+}
+
+// Flags that take precedence over the values from the configuration file.
+#[derive(Args, Debug, Default)]
+pub struct Overrides {
+    /// Address the HTTP server binds to, e.g. 127.0.0.1:8080
+    #[arg(long, global = true)]
+    pub bind: Option<String>,
+
+    /// Directory where the torrent info fetched from peers is cached
+    #[arg(long, global = true)]
+    pub metadata_dir: Option<PathBuf>,
+
+    /// Directory where the downloaded files are written
+    #[arg(long, global = true)]
+    pub output_dir: Option<PathBuf>,
+
+    /// Log file, use "-" to log to stderr
+    #[arg(long, global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// Log level: off, error, warn, info, debug or trace
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+
+    /// Concurrent peer connections for each torrent
+    #[arg(long, global = true)]
+    pub connections: Option<usize>,
+
+    /// Port announced to the trackers
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+
+    /// Default tracker, can be repeated and replaces the configured list
+    #[arg(long = "tracker", global = true)]
+    pub trackers: Vec<String>,
+}
+
+impl Overrides {
+    pub fn apply(self, config: &mut Config) {
+        if let Some(bind) = self.bind {
+            config.server.bind = bind;
+        }
+        if let Some(metadata_dir) = self.metadata_dir {
+            config.storage.metadata_dir = metadata_dir;
+        }
+        if let Some(output_dir) = self.output_dir {
+            config.storage.output_dir = output_dir;
+        }
+        if let Some(log_file) = self.log_file {
+            config.log.file = (log_file.as_os_str() != "-").then_some(log_file);
+        }
+        if let Some(log_level) = self.log_level {
+            config.log.level = log_level;
+        }
+        if let Some(connections) = self.connections {
+            config.peers.connections = connections;
+        }
+        if let Some(port) = self.port {
+            config.peers.port = port;
+        }
+        if !self.trackers.is_empty() {
+            config.trackers.default = self.trackers;
+        }
+    }
+}
+
+impl Cli {
+    pub fn load_config(&mut self) -> Result<Config, ConfigError> {
+        let mut config = match &self.config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+
+        std::mem::take(&mut self.overrides).apply(&mut config);
+        config.log_level()?;
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_config_values() {
+        let mut cli = Cli::parse_from([
+            "rust_bit",
+            "--bind",
+            "0.0.0.0:9000",
+            "--log-file",
+            "-",
+            "--tracker",
+            "udp://tracker:1337/announce",
+            "--tracker",
+            "http://tracker/announce",
+        ]);
+        let config = cli.load_config().unwrap();
+
+        assert_eq!(config.server.bind, "0.0.0.0:9000");
+        assert_eq!(config.log.file, None);
+        assert_eq!(
+            config.trackers.default,
+            vec!["udp://tracker:1337/announce", "http://tracker/announce"]
+        );
+        assert_eq!(config.peers, Config::default().peers);
+    }
+
+    #[test]
+    fn parse_download_command() {
+        let cli = Cli::parse_from([
+            "rust_bit",
+            "download",
+            "file.torrent",
+            "--out",
+            "/tmp/files",
+            "--connections",
+            "2",
+        ]);
+
+        match cli.command {
+            Some(Command::Download(args)) => {
+                assert_eq!(args.source, "file.torrent");
+                assert_eq!(args.out, Some(PathBuf::from("/tmp/files")));
+                assert_eq!(args.timeout, None);
+            }
+            _ => panic!("Download command not parsed"),
+        }
+        assert_eq!(cli.overrides.connections, Some(2));
+    }
+
+    #[test]
+    fn parse_bencode_command() {
+        let cli = Cli::parse_from([
+            "rust_bit",
+            "bencode",
+            "torrent",
+            "set",
+            "file.torrent",
+            "announce",
+            "udp://tracker:1337/announce",
+        ]);
+
+        match cli.command {
+            Some(Command::Bencode(bencode::BencodeArgs {
+                command:
+                    bencode::BencodeCommand::Torrent(bencode::TorrentCommand::Set {
+                        key,
+                        json,
+                        output,
+                        ..
+                    }),
+            })) => {
+                assert_eq!(key, "announce");
+                assert!(!json);
+                assert_eq!(output, None);
+            }
+            _ => panic!("Bencode command not parsed"),
+        }
+    }
+
+    #[test]
+    fn invalid_log_level_override() {
+        let mut cli = Cli::parse_from(["rust_bit", "--log-level", "loud"]);
+
+        assert_eq!(
+            cli.load_config(),
+            Err(ConfigError::LogLevel("loud".to_owned()))
+        );
+    }
+}
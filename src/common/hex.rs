@@ -0,0 +1,35 @@
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_bytes() {
+        assert_eq!(encode(&[0x00, 0xa6, 0xe4, 0x49]), "00a6e449");
+    }
+
+    #[test]
+    fn decode_lower_and_upper_case() {
+        assert_eq!(decode("00A6e449"), Some(vec![0x00, 0xa6, 0xe4, 0x49]));
+    }
+
+    #[test]
+    fn decode_invalid_hex() {
+        assert_eq!(decode("a6e"), None);
+        assert_eq!(decode("zz"), None);
+    }
+}
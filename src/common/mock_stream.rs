@@ -125,6 +125,7 @@ impl MockStream {
 ///     assert_eq!(8, sut.read_data(&mut c));
 /// }
 /// ```
+#[allow(clippy::test_attr_in_doctest)]
 #[derive(Clone)]
 pub struct FailingMockStream {
     kind: ErrorKind,
@@ -208,6 +209,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::unbuffered_bytes)]
     fn test_mock_stream_read_lines() {
         let mut s = MockStream::new();
         s.push_bytes_to_read("abcd\r\ndcba\r\n".as_bytes());
@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use log::LevelFilter;
+use serde::Deserialize;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    #[error("Unable to read config file {0}: {1}")]
+    Read(String, String),
+    #[error("Unable to parse config file {0}: {1}")]
+    Parse(String, String),
+    #[error("Invalid log level {0}")]
+    LogLevel(String),
+}
+
+// Runtime configuration of the client. Every value has a default, so a config file only
+// needs the keys that differ from them.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub log: LogConfig,
+    pub peers: PeersConfig,
+    pub trackers: TrackersConfig,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    // Where the torrent info fetched from the peers is cached
+    pub metadata_dir: PathBuf,
+    // Where the downloaded files are written
+    pub output_dir: PathBuf,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    // Log to stderr when no file is provided
+    pub file: Option<PathBuf>,
+    pub level: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PeersConfig {
+    // Number of concurrent connections to peers for each torrent
+    pub connections: usize,
+    // Port announced to the trackers
+    pub port: u16,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackersConfig {
+    // Trackers added as the last tier of every torrent that is not private, none by default
+    pub default: Vec<String>,
+    // Sent to HTTP trackers, some of them refuse unknown clients
+    pub user_agent: String,
+    // Seconds to connect to an HTTP tracker, then to get its whole answer
+    pub connect_timeout: u64,
+    pub timeout: u64,
+    // Redirects followed before an HTTP announce or scrape fails
+    pub max_redirects: u32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: "127.0.0.1:8080".to_owned(),
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            metadata_dir: PathBuf::from("./downloads"),
+            output_dir: PathBuf::from("."),
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            file: None,
+            level: "debug".to_owned(),
+        }
+    }
+}
+
+impl Default for PeersConfig {
+    fn default() -> Self {
+        PeersConfig {
+            connections: 5,
+            port: 8000,
+        }
+    }
+}
+
+impl Default for TrackersConfig {
+    fn default() -> Self {
+        TrackersConfig {
+            default: vec![],
+            user_agent: concat!("rust_bit/", env!("CARGO_PKG_VERSION")).to_owned(),
+            connect_timeout: 5,
+            timeout: 15,
+            max_redirects: 5,
+        }
+    }
+}
+
+impl Config {
+    // The format is picked from the file extension: JSON for .json files, TOML otherwise.
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let display = path.display().to_string();
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| ConfigError::Read(display.clone(), err.to_string()))?;
+
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension == "json");
+        let config: Config = if is_json {
+            serde_json::from_str(&content)
+                .map_err(|err| ConfigError::Parse(display, err.to_string()))?
+        } else {
+            toml::from_str(&content).map_err(|err| ConfigError::Parse(display, err.to_string()))?
+        };
+
+        config.log_level()?;
+        Ok(config)
+    }
+
+    pub fn log_level(&self) -> Result<LevelFilter, ConfigError> {
+        LevelFilter::from_str(&self.log.level)
+            .map_err(|_| ConfigError::LogLevel(self.log.level.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_config(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_partial_toml_config() {
+        let path = write_config(
+            "rust_bit_partial.toml",
+            "[server]\nbind = \"0.0.0.0:9090\"\n\n[peers]\nconnections = 12\n",
+        );
+        let config = Config::from_file(&path).unwrap();
+
+        assert_eq!(config.server.bind, "0.0.0.0:9090");
+        assert_eq!(config.peers.connections, 12);
+        assert_eq!(config.peers.port, 8000);
+        assert_eq!(config.storage, StorageConfig::default());
+    }
+
+    #[test]
+    fn load_json_config() {
+        let path = write_config(
+            "rust_bit_config.json",
+            r#"{"log": {"file": null, "level": "info"}, "trackers": {"default": []}}"#,
+        );
+        let config = Config::from_file(&path).unwrap();
+
+        assert_eq!(config.log.file, None);
+        assert_eq!(config.log_level(), Ok(LevelFilter::Info));
+        assert!(config.trackers.default.is_empty());
+    }
+
+    #[test]
+    fn load_example_config() {
+        let config: Config = toml::from_str(include_str!("../../config.example.toml")).unwrap();
+
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn reject_unknown_keys() {
+        let path = write_config("rust_bit_unknown.toml", "[server]\nport = 9090\n");
+
+        assert!(matches!(
+            Config::from_file(&path),
+            Err(ConfigError::Parse(_, _))
+        ));
+    }
+
+    #[test]
+    fn reject_invalid_log_level() {
+        let path = write_config("rust_bit_level.toml", "[log]\nlevel = \"loud\"\n");
+
+        assert_eq!(
+            Config::from_file(&path),
+            Err(ConfigError::LogLevel("loud".to_owned()))
+        );
+    }
+}
@@ -1,53 +1,99 @@
 mod actors;
+mod api;
 mod bencode;
+mod cli;
 mod common;
+mod config;
 mod messages;
 mod peer;
 mod torrent;
 mod tracker;
 
+use actors::events::EventBus;
+use actors::torrents_registry::TorrentsRegistryActor;
 use actors::trackers_interface::TrackersInterfaceActor;
 use chrono::Local;
+use clap::Parser;
 use env_logger::Builder;
-use log::LevelFilter;
 
 use std::fs::File;
 use std::io::Write;
 
-use torrent::magnet;
-
 use actix::prelude::*;
-use actix_web::{post, web, App, HttpResponse, HttpServer};
-
-use crate::actors::messages::TorrentRegistered;
-use crate::actors::torrent::TorrentActor;
+use actix_web::{web, App, HttpServer};
 
-#[post("/add/magnet")]
-async fn add_magnet(data: web::Data<AppState>, magnet_raw: String) -> HttpResponse {
-    let magnet = magnet::parse_magnet(magnet_raw.as_bytes().to_vec()).unwrap();
-    let info_hash = magnet.get_info_hash();
+use crate::api::AppState;
+use crate::cli::{Cli, Command};
+use crate::config::Config;
+use crate::tracker::tcp_tracker::HttpClient;
 
-    let addr = TorrentActor::new(info_hash.clone()).start();
-
-    let msg = TorrentRegistered {
-        info_hash,
-        torrent_actor_addr: addr.clone(),
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let mut cli = Cli::parse();
+    let config = match cli.load_config() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
     };
 
-    let _ = data.trackers_interface.try_send(msg);
+    init_logger(&config)?;
 
-    HttpResponse::Ok().body("Test")
+    match cli.command {
+        Some(Command::Download(args)) => {
+            if let Err(err) = cli::download::run(args, config).await {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Command::Create(args)) => {
+            if let Err(err) = cli::create::run(args) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Command::Bencode(args)) => {
+            if let Err(err) = cli::bencode::run(args) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        None => serve(config).await,
+    }
 }
 
-struct AppState {
-    trackers_interface: Addr<TrackersInterfaceActor>,
+async fn serve(config: Config) -> std::io::Result<()> {
+    let events = EventBus::new();
+    let trackers_interface =
+        TrackersInterfaceActor::new(&config.trackers.default, config.peers.port)
+            .http_client(HttpClient::new(&config.trackers))
+            .start();
+    let torrents_registry = TorrentsRegistryActor::new().start();
+
+    let bind = config.server.bind.to_owned();
+    let state = web::Data::new(AppState {
+        trackers_interface,
+        torrents_registry,
+        events,
+        config,
+    });
+
+    HttpServer::new(move || App::new().app_data(state.clone()).configure(api::configure))
+        .bind(bind)?
+        .run()
+        .await
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let trackers_interface = TrackersInterfaceActor::new().start();
+fn init_logger(config: &Config) -> std::io::Result<()> {
+    let target = match &config.log.file {
+        Some(path) => env_logger::Target::Pipe(Box::new(File::create(path)?)),
+        None => env_logger::Target::Stderr,
+    };
 
-    let target = Box::new(File::create("./test.log").expect("Can't create file"));
     Builder::new()
         .format(|buf, record| {
             writeln!(
@@ -60,15 +106,10 @@ async fn main() -> std::io::Result<()> {
                 record.args()
             )
         })
-        .target(env_logger::Target::Pipe(target))
-        .filter(None, LevelFilter::Debug)
+        .target(target)
+        .filter(None, config.log_level().unwrap_or(log::LevelFilter::Debug))
         .filter_module("sqlx::query", log::LevelFilter::Off)
         .init();
 
-    let state = web::Data::new(AppState { trackers_interface });
-
-    HttpServer::new(move || App::new().app_data(state.clone()).service(add_magnet))
-        .bind(("127.0.0.1", 8080))?
-        .run()
-        .await
+    Ok(())
 }
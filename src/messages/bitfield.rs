@@ -52,7 +52,7 @@ mod test {
         let input = vec![];
 
         let outcome = BitfieldMessage::from_bytes(&input);
-        assert_eq!(true, outcome.get_bitfield().is_empty());
+        assert!(outcome.get_bitfield().is_empty());
     }
 
     #[test]
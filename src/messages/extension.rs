@@ -1,18 +1,89 @@
 use std::collections::HashMap;
+use std::fmt;
 
-use log::{error, info};
+use log::{debug, error};
+use serde::de::{IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::bencode::bytes;
+use crate::bencode::de::from_decoder;
 use crate::bencode::decode::Decoder;
-use crate::bencode::encode::Encode;
-use crate::bencode::metainfo::Metainfo;
+use crate::bencode::ser::to_bytes;
+
+// The header comes from peers, anything nested deeper than the extensions map or bigger
+// than a block is refused.
+const MAX_HEADER_DEPTH: usize = 4;
+const MAX_HEADER_SIZE: usize = 16 * 1024;
+
+// Bencoded dictionary at the start of an extension message (BEP 10), the metadata piece
+// of a ut_metadata data message follows it.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct ExtensionHeader {
+    #[serde(
+        rename = "m",
+        default,
+        deserialize_with = "deserialize_extensions",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    extensions: HashMap<String, u8>,
+    metadata_size: Option<usize>,
+    msg_type: Option<usize>,
+    piece: Option<usize>,
+}
+
+// Names are read as bytes and ids as any value, so that an entry that does not fit
+// can be skipped without failing the whole handshake.
+#[derive(Deserialize)]
+struct ExtensionName(#[serde(with = "bytes")] Vec<u8>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExtensionId {
+    Id(i64),
+    Other(IgnoredAny),
+}
+
+// Extensions with a name that is not UTF-8 or an id that is not a byte are ignored, as
+// other peers would not understand them anyway.
+fn deserialize_extensions<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, u8>, D::Error> {
+    deserializer.deserialize_map(ExtensionsVisitor)
+}
+
+struct ExtensionsVisitor;
+
+impl<'de> Visitor<'de> for ExtensionsVisitor {
+    type Value = HashMap<String, u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a dictionary of extension ids")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut extensions = HashMap::new();
+        while let Some((ExtensionName(name), id)) =
+            map.next_entry::<ExtensionName, ExtensionId>()?
+        {
+            let id = match id {
+                ExtensionId::Id(id) => u8::try_from(id).ok(),
+                ExtensionId::Other(_) => None,
+            };
+            match (String::from_utf8(name), id) {
+                (Ok(name), Some(id)) => {
+                    extensions.insert(name, id);
+                }
+                (name, _) => debug!("Skipped extension {:?}", name),
+            }
+        }
+        Ok(extensions)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ExtensionMessage {
     id: u8,
-    msg_type: Option<usize>,
-    metadata_size: Option<usize>,
-    extensions: HashMap<String, u8>,
-    piece: Option<usize>,
+    header: ExtensionHeader,
     data: Vec<u8>,
 }
 
@@ -21,46 +92,37 @@ impl ExtensionMessage {
     pub fn new(extensions: HashMap<String, u8>, metadata_size: usize) -> ExtensionMessage {
         ExtensionMessage {
             id: 20,
-            msg_type: None,
-            metadata_size: Some(metadata_size),
-            extensions,
-            piece: None,
+            header: ExtensionHeader {
+                extensions,
+                metadata_size: Some(metadata_size),
+                ..Default::default()
+            },
             data: vec![],
         }
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<ExtensionMessage, &'static str> {
-        let mut decoder = Decoder::init(bytes[1..].to_vec());
-        let content = match decoder.decode() {
+        let (id, body) = bytes.split_first().ok_or("Empty extension message")?;
+        let mut decoder = Decoder::init(body.to_vec())
+            .max_depth(MAX_HEADER_DEPTH)
+            .max_size(MAX_HEADER_SIZE);
+        let header = match from_decoder(&mut decoder) {
             Ok(value) => value,
             Err(err) => {
-                return {
-                    error!("{:?}", err.to_string());
-                    Err("Error during decoding")
-                }
+                error!("{:?}", err.to_string());
+                return Err("Error during decoding");
             }
         };
 
-        let default = HashMap::new();
-        let extensions = content
-            .get_value_from_dict("m")
-            .ok()
-            .map(|e| e.get_dict_content())
-            .map(|e| e.unwrap_or(&default))
-            .map_or(HashMap::new(), build_extensions_map);
-
         Ok(ExtensionMessage {
-            id: bytes[0],
-            data: bytes[decoder.get_total_parsed_bytes() + 1..].to_vec(),
-            msg_type: content.get_integer_from_dict("msg_type").ok(),
-            metadata_size: content.get_integer_from_dict("metadata_size").ok(),
-            piece: content.get_integer_from_dict("piece").ok(),
-            extensions,
+            id: *id,
+            header,
+            data: body[decoder.get_total_parsed_bytes()..].to_vec(),
         })
     }
 
     pub fn get_metadata_size(&self) -> Option<usize> {
-        self.metadata_size
+        self.header.metadata_size
     }
 
     pub fn get_data(&self) -> Vec<u8> {
@@ -68,42 +130,29 @@ impl ExtensionMessage {
     }
 
     pub fn get_extensions(&self) -> &HashMap<String, u8> {
-        &self.extensions
+        &self.header.extensions
     }
 
     pub fn is_handshake(&self) -> bool {
-        self.metadata_size.is_some()
+        self.header.metadata_size.is_some()
     }
 
-    pub fn is_data(&self) -> bool {
-        matches!(self.msg_type, Some(1))
+    pub fn get_piece(&self) -> Option<usize> {
+        self.header.piece
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let mut body_raw: HashMap<String, usize> = HashMap::from([]);
-
-        if let Some(piece) = self.piece {
-            body_raw.insert("piece".to_owned(), piece);
-        }
-
-        if let Some(msg_type) = self.msg_type {
-            body_raw.insert("msg_type".to_owned(), msg_type);
-        }
+    pub fn is_data(&self) -> bool {
+        matches!(self.header.msg_type, Some(1))
+    }
 
-        [vec![self.id], body_raw.encode()].concat()
+    pub fn is_reject(&self) -> bool {
+        matches!(self.header.msg_type, Some(2))
     }
-}
 
-fn build_extensions_map(metainfo_map: &HashMap<String, Metainfo>) -> HashMap<String, u8> {
-    let mut extension: HashMap<String, u8> = HashMap::new();
-    for (key, metainfo_value) in metainfo_map {
-        if let Ok(value) = metainfo_value.get_integer_content() {
-            extension.insert(key.to_owned(), value as u8);
-        } else {
-            info!("No extension value found from {:?}", metainfo_value);
-        }
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let header = to_bytes(&self.header).expect("Extension header is always serializable");
+        [vec![self.id], header].concat()
     }
-    extension
 }
 
 #[cfg(test)]
@@ -114,15 +163,16 @@ mod test {
     fn test_as_bytes() {
         let outcome = ExtensionMessage {
             id: 1,
-            msg_type: Some(1),
-            metadata_size: None,
-            extensions: HashMap::new(),
-            piece: Some(2),
+            header: ExtensionHeader {
+                msg_type: Some(1),
+                piece: Some(2),
+                ..Default::default()
+            },
             data: vec![],
         }
         .as_bytes();
 
-        let expected = [&vec![0x01], "d8:msg_typei1e5:piecei2ee".as_bytes()].concat();
+        let expected = [&[0x01], "d8:msg_typei1e5:piecei2ee".as_bytes()].concat();
         assert_eq!(expected, outcome)
     }
 
@@ -137,8 +187,24 @@ mod test {
             &HashMap::from([("foo".to_owned(), 2)]),
             outcome.get_extensions()
         );
-        assert_eq!(true, outcome.get_data().is_empty());
-        assert_eq!(None, outcome.msg_type);
+        assert!(outcome.get_data().is_empty());
+        assert_eq!(None, outcome.header.msg_type);
+    }
+
+    #[test]
+    fn test_from_bytes_handshake_skips_unusable_extensions() {
+        let input = [
+            vec![20],
+            b"d13:metadata_sizei1024e1:md2:\xff\xfei1e3:bari300e3:bazli1ee3:fooi2eee".to_vec(),
+        ]
+        .concat();
+
+        let outcome = ExtensionMessage::from_bytes(&input).unwrap();
+        assert_eq!(Some(1024), outcome.get_metadata_size());
+        assert_eq!(
+            &HashMap::from([("foo".to_owned(), 2)]),
+            outcome.get_extensions()
+        );
     }
 
     #[test]
@@ -156,7 +222,7 @@ mod test {
         assert_eq!(None, outcome.get_metadata_size());
         assert_eq!(&HashMap::from([]), outcome.get_extensions());
         assert_eq!(data, outcome.get_data());
-        assert_eq!(Some(1), outcome.msg_type);
-        assert_eq!(Some(2), outcome.piece);
+        assert_eq!(Some(1), outcome.header.msg_type);
+        assert_eq!(Some(2), outcome.header.piece);
     }
 }
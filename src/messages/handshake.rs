@@ -50,7 +50,7 @@ mod test {
 
     #[test]
     fn test_handshake_message() {
-        let outcome = HandshakeMessage::new(&vec![0x00, 0x01], "peer").as_bytes();
+        let outcome = HandshakeMessage::new(&[0x00, 0x01], "peer").as_bytes();
         let expect = vec![
             0x13, 0x42, 0x69, 0x74, 0x54, 0x6f, 0x72, 0x72, 0x65, 0x6e, 0x74, 0x20, 0x70, 0x72,
             0x6f, 0x74, 0x6f, 0x63, 0x6f, 0x6c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00,
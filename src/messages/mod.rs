@@ -95,15 +95,6 @@ impl Message {
         .concat()
     }
 
-    pub fn is_extension_data_message(&self) -> bool {
-        if let ContentType::Extension(msg) = &self.content {
-            if msg.is_data() {
-                return true;
-            }
-        }
-        false
-    }
-
     pub fn is_request_message(&self) -> bool {
         matches!(self.content, ContentType::Request(_))
     }
@@ -172,7 +163,7 @@ mod test {
     #[test]
     fn test_new_interested() {
         let outcome = new_interested().as_bytes();
-        let expect = [(1 as u32).to_be_bytes().to_vec(), vec![0x02]].concat();
+        let expect = [1_u32.to_be_bytes().to_vec(), vec![0x02]].concat();
         assert_eq!(outcome, expect);
     }
 
@@ -181,11 +172,11 @@ mod test {
         let outcome = new_request(10, 2, 4).as_bytes();
 
         let expect = [
-            (13 as u32).to_be_bytes().to_vec(),
+            13_u32.to_be_bytes().to_vec(),
             [0x06].to_vec(),
-            (10 as u32).to_be_bytes().to_vec(),
-            (2 as u32).to_be_bytes().to_vec(),
-            (4 as u32).to_be_bytes().to_vec(),
+            10_u32.to_be_bytes().to_vec(),
+            2_u32.to_be_bytes().to_vec(),
+            4_u32.to_be_bytes().to_vec(),
         ]
         .concat();
         assert_eq!(outcome, expect);
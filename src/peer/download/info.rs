@@ -1,15 +1,93 @@
+use log::debug;
+
 use crate::{
-    messages::{new_metadata, Message},
+    messages::{new_metadata, ContentType, Message},
     peer::Peer,
 };
 
-pub fn next_piece() -> impl FnMut(&mut Peer, usize) {
-    |peer: &mut Peer, piece_index| {
-        let metadata_id = peer.get_extension_id_by_name("ut_metadata");
-        peer.send_message(new_metadata(metadata_id, piece_index))
+use super::{DownloadableError, INFO_PIECE_SIZE};
+
+// Upper bound on the metadata size announced by a peer, bigger values are refused before
+// allocating anything.
+pub const MAX_METADATA_SIZE: usize = 8 * 1024 * 1024;
+
+// Download the metadata piece by piece as described by BEP 9. Pieces are stored by the
+// index found in the response, so they can arrive in any order, and a reject from the peer
+// stops the download right away.
+pub fn download(peer: &mut Peer) -> Result<Vec<u8>, DownloadableError> {
+    let metadata_size = peer.get_metadata_size();
+    if metadata_size == 0 || metadata_size > MAX_METADATA_SIZE {
+        return Err(DownloadableError::InvalidMetadataSize(metadata_size));
+    }
+
+    let piece_count = metadata_size.div_ceil(INFO_PIECE_SIZE);
+    let mut pieces: Vec<Option<Vec<u8>>> = vec![None; piece_count];
+    let mut requested = None;
+    let mut idle_count = 0;
+
+    loop {
+        if let Some(message) = peer.read_message() {
+            peer.apply_message(&message);
+            idle_count = 0;
+
+            if let Some(piece) = apply_metadata_message(&message, &mut pieces, metadata_size)? {
+                if requested == Some(piece) {
+                    requested = None;
+                }
+            }
+        }
+
+        if pieces.iter().all(|piece| piece.is_some()) {
+            return Ok(pieces.into_iter().flatten().flatten().collect());
+        }
+
+        if requested.is_none() {
+            let next = pieces.iter().position(|piece| piece.is_none());
+            if let Some(piece_index) = next {
+                let metadata_id = peer.get_extension_id_by_name("ut_metadata");
+                peer.send_message(new_metadata(metadata_id, piece_index));
+                requested = Some(piece_index);
+            }
+        }
+
+        idle_count += 1;
+
+        if idle_count > 10 {
+            return Err(DownloadableError::Idle());
+        }
     }
 }
 
-pub fn message_filter() -> fn(&Message) -> bool {
-    Message::is_extension_data_message
+// Store the data of a metadata piece and return its index. Pieces out of range, already
+// received or with an unexpected length are discarded.
+fn apply_metadata_message(
+    message: &Message,
+    pieces: &mut [Option<Vec<u8>>],
+    metadata_size: usize,
+) -> Result<Option<usize>, DownloadableError> {
+    let extension = match message.get_content() {
+        ContentType::Extension(extension) => extension,
+        _ => return Ok(None),
+    };
+
+    if extension.is_reject() {
+        return Err(DownloadableError::MetadataRejected(
+            extension.get_piece().unwrap_or_default(),
+        ));
+    }
+
+    let piece_index = match extension.get_piece() {
+        Some(piece_index) if extension.is_data() && piece_index < pieces.len() => piece_index,
+        _ => return Ok(None),
+    };
+
+    let data = extension.get_data();
+    let expected_length = (metadata_size - piece_index * INFO_PIECE_SIZE).min(INFO_PIECE_SIZE);
+    if data.len() != expected_length || pieces[piece_index].is_some() {
+        debug!("Discarded metadata piece {:?}", piece_index);
+        return Ok(None);
+    }
+
+    pieces[piece_index] = Some(data);
+    Ok(Some(piece_index))
 }
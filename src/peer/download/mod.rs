@@ -18,20 +18,16 @@ pub enum DownloadableError {
     ChokedPeer(),
     #[error("Download idle for too long")]
     Idle(),
+    #[error("Metadata piece {0} rejected by peer")]
+    MetadataRejected(usize),
+    #[error("Invalid metadata size {0}")]
+    InvalidMetadataSize(usize),
 }
 
 impl Downloadable {
     pub fn download(&self, peer: &mut Peer) -> Result<Vec<u8>, DownloadableError> {
         match self {
-            Downloadable::Info => {
-                let buffer = MessageBuffer::new(
-                    info::message_filter(),
-                    info::next_piece(),
-                    (0..peer.get_metadata_size()).step_by(INFO_PIECE_SIZE).len(),
-                );
-
-                execute(peer, buffer)
-            }
+            Downloadable::Info => info::download(peer),
             Downloadable::Block((piece_length, piece_index, total_length)) => {
                 let buffer = MessageBuffer::new(
                     block::message_filter(),
@@ -92,25 +88,87 @@ mod test {
 
     use super::*;
 
+    fn metadata_message(msg_type: usize, piece: usize, data: &[u8]) -> Vec<u8> {
+        let dictionary = format!("d8:msg_typei{}e5:piecei{}ee", msg_type, piece);
+        let length = (2 + dictionary.len() + data.len()) as u32;
+
+        [
+            length.to_be_bytes().to_vec(),
+            vec![20, 2],
+            dictionary.into_bytes(),
+            data.to_vec(),
+        ]
+        .concat()
+    }
+
+    fn metadata_peer(s: &MockStream, metadata_size: usize) -> Peer {
+        let mut peer = Peer::new(StreamInterface::Mocked(s.clone()), &[]);
+        peer.set_metadata_size(metadata_size);
+        peer.add_extension("ut_metadata".to_owned(), 1);
+        peer
+    }
+
     #[test]
     fn test_download_info() {
         let mut s = MockStream::new();
         // UNCHOKE MESSAGE
         s.push_bytes_to_read([0, 0, 0, 1, 1].as_slice());
         // EXTENSION DATA MESSAGE
-        let dictionary = "d8:msg_typei1ee".as_bytes().to_vec();
-        let message = [vec![0, 0, 0, 22, 20, 2], dictionary, vec![1, 2, 3, 4, 5]].concat();
-        s.push_bytes_to_read(&message);
+        s.push_bytes_to_read(&metadata_message(1, 0, &[1, 2, 3, 4, 5]));
 
-        let e = StreamInterface::Mocked(s.clone());
+        let mut peer = metadata_peer(&s, 5);
 
-        let mut peer = Peer::new(e, &[]);
+        let downloadable = Downloadable::Info;
+        assert_eq!(downloadable.download(&mut peer), Ok(vec![1, 2, 3, 4, 5]));
+    }
 
-        peer.set_metadata_size(INFO_PIECE_SIZE);
-        peer.add_extension("ut_metadata".to_owned(), 1);
+    #[test]
+    fn test_download_info_pieces_out_of_order() {
+        let first = vec![1; INFO_PIECE_SIZE];
+        let second = vec![2; 10];
+
+        let mut s = MockStream::new();
+        s.push_bytes_to_read(&metadata_message(1, 1, &second));
+        // Same piece twice and a piece out of range are discarded
+        s.push_bytes_to_read(&metadata_message(1, 1, &[3; 10]));
+        s.push_bytes_to_read(&metadata_message(1, 5, &[3; 10]));
+        s.push_bytes_to_read(&metadata_message(1, 0, &first));
+
+        let mut peer = metadata_peer(&s, INFO_PIECE_SIZE + 10);
 
         let downloadable = Downloadable::Info;
-        assert_eq!(downloadable.download(&mut peer), Ok(vec![1, 2, 3, 4, 5]));
+        assert_eq!(
+            downloadable.download(&mut peer),
+            Ok([first, second].concat())
+        );
+    }
+
+    #[test]
+    fn test_download_info_rejected() {
+        let mut s = MockStream::new();
+        s.push_bytes_to_read(&metadata_message(2, 0, &[]));
+
+        let mut peer = metadata_peer(&s, 5);
+
+        let downloadable = Downloadable::Info;
+        assert_eq!(
+            downloadable.download(&mut peer),
+            Err(DownloadableError::MetadataRejected(0))
+        );
+    }
+
+    #[test]
+    fn test_download_info_too_big() {
+        let s = MockStream::new();
+        let mut peer = metadata_peer(&s, info::MAX_METADATA_SIZE + 1);
+
+        let downloadable = Downloadable::Info;
+        assert_eq!(
+            downloadable.download(&mut peer),
+            Err(DownloadableError::InvalidMetadataSize(
+                info::MAX_METADATA_SIZE + 1
+            ))
+        );
     }
 
     #[test]
@@ -1,4 +1,7 @@
-use log::{debug, error, info};
+use std::net::SocketAddr;
+
+use log::{debug, info};
+use sha1::{Digest, Sha1};
 
 use crate::messages::{new_handshake, new_interested};
 use crate::peer::Peer;
@@ -19,6 +22,8 @@ pub enum PeerManagerError {
     PieceDownloadFailure(String),
     #[error("Unsuccessfull piece verification")]
     PieceVerificationFailure(),
+    #[error("Metadata does not match the info hash")]
+    MetadataVerificationFailure(),
     #[error(transparent)]
     Stream(#[from] StreamError),
     #[error(transparent)]
@@ -27,25 +32,33 @@ pub enum PeerManagerError {
     Download(#[from] DownloadableError),
 }
 
-pub fn get_info(info_hash: &[u8], endpoint: &str) -> Result<Info, PeerManagerError> {
+pub fn get_info(info_hash: &[u8], endpoint: SocketAddr) -> Result<Info, PeerManagerError> {
     let stream = StreamInterface::connect(endpoint, false)?;
     let mut peer = Peer::new(stream, info_hash);
 
     init_peer(&mut peer)?;
     let info = Downloadable::Info.download(&mut peer)?;
 
-    let info = Info::from_bytes(info)?;
-    Ok(info)
+    verify_info(info_hash, info)
+}
+
+// The metadata is trusted only when its SHA-1 is the info hash we asked for.
+pub fn verify_info(info_hash: &[u8], bytes: Vec<u8>) -> Result<Info, PeerManagerError> {
+    if Sha1::digest(&bytes).as_slice() != info_hash {
+        return Err(PeerManagerError::MetadataVerificationFailure());
+    }
+
+    Ok(Info::from_bytes(bytes)?)
 }
 
 struct Context {
-    pub endpoint: String,
+    pub endpoint: SocketAddr,
     pub peer_id: String,
     pub piece_idx: usize,
 }
 
 impl Context {
-    fn new(peer_id: String, piece_idx: usize, endpoint: String) -> Self {
+    fn new(peer_id: String, piece_idx: usize, endpoint: SocketAddr) -> Self {
         Context {
             peer_id,
             piece_idx,
@@ -55,17 +68,17 @@ impl Context {
 }
 
 pub fn download(
-    endpoint: String,
+    endpoint: SocketAddr,
     info: &Info,
     piece_idx: usize,
 ) -> Result<Vec<u8>, PeerManagerError> {
-    let stream = StreamInterface::connect(&endpoint, false)?;
+    let stream = StreamInterface::connect(endpoint, false)?;
     let mut peer = Peer::new(stream, &info.compute_info_hash());
     init_peer(&mut peer)?;
 
     let piece_length = info.get_piece_length();
 
-    let ctx = Context::new(peer.get_peer_id(), piece_idx, endpoint.to_owned());
+    let ctx = Context::new(peer.get_peer_id(), piece_idx, endpoint);
 
     track_progress(PieceEventType::StartDownload(), &ctx);
 
@@ -127,3 +140,30 @@ fn track_progress(event_type: PieceEventType, ctx: &Context) {
         ),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INFO: &str = "d6:lengthi3e4:name1:a12:piece lengthi4e6:pieces20:abcdefghijklmnopqrste";
+
+    #[test]
+    fn verify_matching_info() {
+        let info_hash = Sha1::digest(INFO.as_bytes()).to_vec();
+
+        let info = verify_info(&info_hash, INFO.as_bytes().to_vec()).unwrap();
+
+        assert_eq!(info.compute_info_hash(), info_hash);
+    }
+
+    #[test]
+    fn verify_tampered_info() {
+        let info_hash = Sha1::digest(INFO.as_bytes()).to_vec();
+        let tampered = INFO.replace("1:a", "1:b");
+
+        assert_eq!(
+            verify_info(&info_hash, tampered.into_bytes()),
+            Err(PeerManagerError::MetadataVerificationFailure())
+        );
+    }
+}
@@ -30,6 +30,13 @@ impl PiecePool {
         }
     }
 
+    pub fn len(&self) -> usize {
+        match self.pool().lock() {
+            Ok(piece_pool) => piece_pool.len(),
+            Err(_) => 0,
+        }
+    }
+
     #[cfg(test)]
     pub fn is_emtpy(&self) -> bool {
         match self.pool().lock() {
@@ -69,4 +76,12 @@ mod test {
         pool.insert(0);
         assert!(!pool.is_emtpy());
     }
+
+    #[test]
+    fn test_len() {
+        let pool = PiecePool::new(3);
+        pool.pop();
+
+        assert_eq!(pool.len(), 2);
+    }
 }
@@ -1,6 +1,6 @@
 use std::io;
 use std::io::prelude::*;
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream};
 use std::time::Duration;
 use std::{thread, time, vec};
 
@@ -52,15 +52,13 @@ pub enum StreamError {
 }
 
 impl StreamInterface {
-    pub fn connect(endpoint: &str, mocked: bool) -> Result<Self, StreamError> {
+    pub fn connect(endpoint: SocketAddr, mocked: bool) -> Result<Self, StreamError> {
         if mocked {
             return Ok(StreamInterface::Mocked(MockStream::new()));
         }
 
-        let server: std::net::SocketAddr =
-            endpoint.parse().expect("Unable to parse socket address");
         let connect_timeout = Duration::from_secs(1);
-        let stream = TcpStream::connect_timeout(&server, connect_timeout)
+        let stream = TcpStream::connect_timeout(&endpoint, connect_timeout)
             .map_err(|_| StreamError::EstablishConnection())?;
         Ok(StreamInterface::Tcp(stream))
     }
@@ -176,7 +174,7 @@ mod test {
         s.push_bytes_to_read([1].as_slice());
         let mut e = StreamInterface::Mocked(s);
 
-        assert_eq!(has_messages(&mut e), true);
+        assert!(has_messages(&mut e));
     }
 
     #[test]
@@ -185,7 +183,7 @@ mod test {
         s.push_bytes_to_read([].as_slice());
         let mut e = StreamInterface::Mocked(s);
 
-        assert_eq!(has_messages(&mut e), false);
+        assert!(!has_messages(&mut e));
     }
 
     #[test]
@@ -195,11 +193,11 @@ mod test {
         let mut e = StreamInterface::Mocked(s);
 
         let length = 1;
-        let mut buffer = vec![0; length as usize];
+        let mut buffer = vec![0; length];
 
         let expect: Vec<u8> = [1].to_vec();
 
-        assert_eq!(peek_and_read(&mut e, &mut buffer, length), true);
+        assert!(peek_and_read(&mut e, &mut buffer, length));
         assert_eq!(buffer, expect)
     }
 
@@ -210,11 +208,11 @@ mod test {
         let mut e = StreamInterface::Mocked(s);
 
         let length = 0;
-        let mut buffer = vec![0; length as usize];
+        let mut buffer = vec![0; length];
 
         let expect: Vec<u8> = [].to_vec();
 
-        assert_eq!(peek_and_read(&mut e, &mut buffer, length), true);
+        assert!(peek_and_read(&mut e, &mut buffer, length));
         assert_eq!(buffer, expect)
     }
 
@@ -225,11 +223,11 @@ mod test {
         let mut e = StreamInterface::Mocked(s);
 
         let length = 3;
-        let mut buffer = vec![0; length as usize];
+        let mut buffer = vec![0; length];
 
         let expect: Vec<u8> = [1, 2, 3].to_vec();
 
-        assert_eq!(peek_and_read(&mut e, &mut buffer, length), true);
+        assert!(peek_and_read(&mut e, &mut buffer, length));
         assert_eq!(buffer, expect)
     }
 
@@ -0,0 +1,355 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use sha1::{Digest, Sha1};
+
+use crate::torrent::file::File;
+use crate::torrent::info::Info;
+use crate::torrent::metafile::Metafile;
+
+const MIN_PIECE_LENGTH: usize = 16 * 1024;
+const MAX_PIECE_LENGTH: usize = 16 * 1024 * 1024;
+const TARGET_PIECE_COUNT: usize = 1500;
+
+pub const CREATED_BY: &str = concat!("rust_bit/", env!("CARGO_PKG_VERSION"));
+
+#[derive(thiserror::Error, Debug)]
+pub enum BuilderError {
+    #[error("Error accessing {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("No file found at {0}")]
+    NoFiles(String),
+    #[error("Path {0} is not valid UTF-8")]
+    InvalidPath(String),
+    #[error("Piece length must be a power of two between 16 KiB and 16 MiB")]
+    InvalidPieceLength(),
+}
+
+// Create the metainfo of a local file or directory. Every file below a directory is
+// included, ordered by path, and the pieces are hashed across file boundaries.
+pub struct TorrentBuilder {
+    path: PathBuf,
+    piece_length: Option<usize>,
+    announce_list: Vec<Vec<String>>,
+    comment: Option<String>,
+    created_by: Option<String>,
+    creation_date: Option<usize>,
+    private: bool,
+}
+
+impl TorrentBuilder {
+    pub fn new(path: impl Into<PathBuf>) -> TorrentBuilder {
+        TorrentBuilder {
+            path: path.into(),
+            piece_length: None,
+            announce_list: vec![],
+            comment: None,
+            created_by: Some(CREATED_BY.to_owned()),
+            creation_date: None,
+            private: false,
+        }
+    }
+
+    // When not given, the piece length is chosen from the total length of the files. A given
+    // one must be a power of two between 16 KiB and 16 MiB, as a whole piece is kept in memory.
+    pub fn piece_length(mut self, piece_length: usize) -> TorrentBuilder {
+        self.piece_length = Some(piece_length);
+        self
+    }
+
+    // Each call adds a tier of trackers, as in the announce-list of BEP 12.
+    pub fn announce_tier(mut self, trackers: Vec<String>) -> TorrentBuilder {
+        if !trackers.is_empty() {
+            self.announce_list.push(trackers);
+        }
+        self
+    }
+
+    pub fn comment(mut self, comment: String) -> TorrentBuilder {
+        self.comment = Some(comment);
+        self
+    }
+
+    pub fn creation_date(mut self, timestamp: usize) -> TorrentBuilder {
+        self.creation_date = Some(timestamp);
+        self
+    }
+
+    pub fn private(mut self, private: bool) -> TorrentBuilder {
+        self.private = private;
+        self
+    }
+
+    pub fn build(self) -> Result<Metafile, BuilderError> {
+        let name = file_name(&self.path)?;
+        let is_dir = self.path.is_dir();
+
+        let paths = if is_dir {
+            let mut paths = vec![];
+            walk(&self.path, &mut paths)?;
+            paths.sort();
+            paths
+        } else {
+            vec![self.path.to_owned()]
+        };
+
+        let mut files = vec![];
+        for path in paths.iter() {
+            let metadata = std::fs::metadata(path).map_err(|err| io_error(path, err))?;
+            let relative_path = match is_dir {
+                true => path_components(path.strip_prefix(&self.path).unwrap_or(path))?,
+                false => vec![name.to_owned()],
+            };
+            files.push(File::new(relative_path, metadata.len() as usize));
+        }
+
+        if files.is_empty() {
+            return Err(BuilderError::NoFiles(self.path.display().to_string()));
+        }
+
+        let total_length = files.iter().map(|file| file.get_length()).sum();
+        let piece_length = match self.piece_length {
+            Some(piece_length) if is_valid_piece_length(piece_length) => piece_length,
+            Some(_) => return Err(BuilderError::InvalidPieceLength()),
+            None => pick_piece_length(total_length),
+        };
+        let pieces = hash_pieces(&paths, piece_length)?;
+
+        let (files, length) = match is_dir {
+            true => (Some(files), None),
+            false => (None, Some(total_length)),
+        };
+        let info = Info::new(name, piece_length, pieces, files, length, self.private);
+
+        Ok(Metafile::new(
+            info,
+            self.announce_list,
+            self.comment,
+            self.created_by,
+            self.creation_date,
+        ))
+    }
+}
+
+// The smallest power of two, between 16 KiB and 16 MiB, that keeps the number of pieces
+// around the target.
+fn pick_piece_length(total_length: usize) -> usize {
+    let mut piece_length = MIN_PIECE_LENGTH;
+    while total_length / piece_length > TARGET_PIECE_COUNT && piece_length < MAX_PIECE_LENGTH {
+        piece_length *= 2;
+    }
+    piece_length
+}
+
+fn is_valid_piece_length(piece_length: usize) -> bool {
+    piece_length.is_power_of_two() && (MIN_PIECE_LENGTH..=MAX_PIECE_LENGTH).contains(&piece_length)
+}
+
+// Files are read one after the other as a single stream, the same layout used by the
+// writer when the pieces are split back into the files.
+fn hash_pieces(paths: &[PathBuf], piece_length: usize) -> Result<Vec<u8>, BuilderError> {
+    let mut pieces = vec![];
+    let mut piece = Vec::with_capacity(piece_length);
+
+    for path in paths {
+        let mut file = std::fs::File::open(path).map_err(|err| io_error(path, err))?;
+
+        loop {
+            let missing = piece_length - piece.len();
+            let read = (&mut file)
+                .take(missing as u64)
+                .read_to_end(&mut piece)
+                .map_err(|err| io_error(path, err))?;
+
+            if piece.len() == piece_length {
+                pieces.extend_from_slice(&Sha1::digest(&piece));
+                piece.clear();
+            }
+
+            if read < missing {
+                break;
+            }
+        }
+    }
+
+    if !piece.is_empty() {
+        pieces.extend_from_slice(&Sha1::digest(&piece));
+    }
+
+    Ok(pieces)
+}
+
+// Symlinks to files are included, symlinks to directories are skipped as they may loop.
+fn walk(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), BuilderError> {
+    let entries = std::fs::read_dir(dir).map_err(|err| io_error(dir, err))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|err| io_error(dir, err))?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|err| io_error(&path, err))?;
+
+        if file_type.is_dir() {
+            walk(&path, paths)?;
+        } else if file_type.is_file() || (file_type.is_symlink() && path.is_file()) {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn file_name(path: &Path) -> Result<String, BuilderError> {
+    let canonical = path.canonicalize().map_err(|err| io_error(path, err))?;
+
+    canonical
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_owned())
+        .ok_or_else(|| BuilderError::InvalidPath(path.display().to_string()))
+}
+
+fn path_components(path: &Path) -> Result<Vec<String>, BuilderError> {
+    path.components()
+        .map(|component| {
+            component
+                .as_os_str()
+                .to_str()
+                .map(|component| component.to_owned())
+                .ok_or_else(|| BuilderError::InvalidPath(path.display().to_string()))
+        })
+        .collect()
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> BuilderError {
+    BuilderError::Io(path.display().to_string(), err)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::bencode::encode::Encode;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust_bit_builder_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn pick_piece_length_for_sizes() {
+        assert_eq!(pick_piece_length(1024), MIN_PIECE_LENGTH);
+        assert_eq!(pick_piece_length(4 * 1024 * 1024 * 1024), 4 * 1024 * 1024);
+        assert_eq!(pick_piece_length(usize::MAX), MAX_PIECE_LENGTH);
+    }
+
+    #[test]
+    fn build_single_file() {
+        let dir = temp_dir("single");
+        let path = dir.join("data.bin");
+        let data: Vec<u8> = (0..2 * MIN_PIECE_LENGTH + 3).map(|i| i as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let metafile = TorrentBuilder::new(&path)
+            .piece_length(MIN_PIECE_LENGTH)
+            .announce_tier(vec!["udp://tracker:80".to_owned()])
+            .comment("test".to_owned())
+            .build()
+            .unwrap();
+        let info = metafile.get_info();
+
+        assert_eq!(info.get_name(), "data.bin");
+        assert_eq!(info.get_total_length(), 2 * MIN_PIECE_LENGTH + 3);
+        assert_eq!(info.get_piece_count(), 3);
+        assert!(info.verify_piece(&data[..MIN_PIECE_LENGTH], 0));
+        assert!(info.verify_piece(&data[MIN_PIECE_LENGTH..2 * MIN_PIECE_LENGTH], 1));
+        assert!(info.verify_piece(&data[2 * MIN_PIECE_LENGTH..], 2));
+
+        let parsed = Metafile::from_bytes(metafile.encode()).unwrap();
+        assert_eq!(parsed, metafile);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn build_directory_hashing_across_files() {
+        let dir = temp_dir("directory");
+        let first = vec![b'b'; MIN_PIECE_LENGTH + 2];
+        let second = vec![b'a'; MIN_PIECE_LENGTH + 1];
+        std::fs::write(dir.join("b.txt"), &first).unwrap();
+        std::fs::write(dir.join("nested").join("a.txt"), &second).unwrap();
+
+        let metafile = TorrentBuilder::new(&dir)
+            .piece_length(MIN_PIECE_LENGTH)
+            .private(true)
+            .build()
+            .unwrap();
+        let info = metafile.get_info();
+
+        assert_eq!(info.get_name(), "rust_bit_builder_directory");
+        assert_eq!(
+            info.get_files().unwrap(),
+            vec![
+                File::new(vec!["b.txt".to_owned()], MIN_PIECE_LENGTH + 2),
+                File::new(
+                    vec!["nested".to_owned(), "a.txt".to_owned()],
+                    MIN_PIECE_LENGTH + 1
+                ),
+            ]
+        );
+        let stream = [first, second].concat();
+        assert!(info.verify_piece(&stream[..MIN_PIECE_LENGTH], 0));
+        assert!(info.verify_piece(&stream[MIN_PIECE_LENGTH..2 * MIN_PIECE_LENGTH], 1));
+        assert!(info.verify_piece(&stream[2 * MIN_PIECE_LENGTH..], 2));
+        assert!(metafile.encode().ends_with(b"7:privatei1eee"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn build_empty_directory() {
+        let dir = temp_dir("empty");
+
+        let result = TorrentBuilder::new(&dir).build();
+
+        assert!(matches!(result, Err(BuilderError::NoFiles(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reject_invalid_piece_lengths() {
+        let dir = temp_dir("piece_length");
+        std::fs::write(dir.join("data.bin"), b"hello").unwrap();
+
+        for piece_length in [0, 4, MIN_PIECE_LENGTH + 1, 2 * MAX_PIECE_LENGTH, usize::MAX] {
+            let result = TorrentBuilder::new(&dir).piece_length(piece_length).build();
+            assert!(
+                matches!(result, Err(BuilderError::InvalidPieceLength())),
+                "{piece_length}"
+            );
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn skip_symlinked_directories() {
+        let dir = temp_dir("symlink");
+        std::fs::write(dir.join("nested").join("a.txt"), b"hello").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("nested").join("loop")).unwrap();
+        std::os::unix::fs::symlink(dir.join("nested").join("a.txt"), dir.join("link.txt")).unwrap();
+
+        let metafile = TorrentBuilder::new(&dir).build().unwrap();
+
+        assert_eq!(
+            metafile.get_info().get_files().unwrap(),
+            vec![
+                File::new(vec!["link.txt".to_owned()], 5),
+                File::new(vec!["nested".to_owned(), "a.txt".to_owned()], 5),
+            ]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
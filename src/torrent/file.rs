@@ -1,34 +1,15 @@
-use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
 
-use crate::bencode::encode::{encode_dict_entry, Encode};
-use crate::bencode::metainfo::{Metainfo, MetainfoError};
+use serde::{Deserialize, Serialize};
 
+// Entry of the files list of a multi-file torrent, its wire form is derived.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct File {
     path: Vec<String>,
     length: usize,
 }
 
-#[derive(thiserror::Error, Debug, Clone, PartialEq)]
-pub enum FileError {
-    #[error("Error handling metainfo")]
-    MetainfoError(#[from] MetainfoError),
-}
-
 impl File {
-    pub fn from_metainfo(file: &Metainfo) -> Result<File, FileError> {
-        let file_length = file.get_integer_from_dict("length")?;
-        let file_path_metainfo = file.get_list_from_dict("path")?;
-        let mut file_path = Vec::new();
-
-        for a in file_path_metainfo {
-            let path_value = a.get_string_content()?;
-            file_path.push(path_value);
-        }
-
-        Ok(File::new(file_path, file_length))
-    }
-
     pub fn new(path: Vec<String>, length: usize) -> File {
         File { path, length }
     }
@@ -40,26 +21,31 @@ impl File {
     pub fn get_path(&self) -> Vec<String> {
         self.path.to_vec()
     }
-}
 
-impl Encode for File {
-    fn encode(&self) -> Vec<u8> {
-        let length = encode_dict_entry("length", &self.length);
-        let path = encode_dict_entry("path", &self.path);
-
-        [
-            "d".as_bytes(),
-            length.as_slice(),
-            path.as_slice(),
-            "e".as_bytes(),
-        ]
-        .concat()
+    pub fn has_safe_path(&self) -> bool {
+        !self.path.is_empty()
+            && self
+                .path
+                .iter()
+                .all(|component| is_safe_component(component))
     }
 }
 
+// A path component coming from a torrent must be a plain name: not empty, not . or .. and
+// without separators, so that it can't lead out of the output directory.
+pub fn is_safe_component(component: &str) -> bool {
+    let mut components = Path::new(component).components();
+
+    !component.contains(['/', '\\', '\0'])
+        && matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::bencode::de::from_bytes;
+    use crate::bencode::ser::to_bytes;
 
     #[test]
     fn create_new_file() {
@@ -83,12 +69,12 @@ mod test {
 
         let expected_output = b"d6:lengthi234e4:pathl4:/bin4:/var9:/dump.txtee";
 
-        assert_eq!(file.encode(), expected_output);
+        assert_eq!(to_bytes(&file).unwrap(), expected_output);
     }
 
     #[test]
     fn encode_file_with_empty_path() {
-        let path = vec![];
+        let path = [];
         let length = 12;
 
         let file = File {
@@ -98,6 +84,34 @@ mod test {
 
         let expected_output = b"d6:lengthi12e4:pathlee";
 
-        assert_eq!(file.encode(), expected_output);
+        assert_eq!(to_bytes(&file).unwrap(), expected_output);
+    }
+
+    #[test]
+    fn decode_file() {
+        let file: File = from_bytes(b"d6:lengthi234e3:md53:abc4:pathl3:dir8:file.txtee").unwrap();
+
+        assert_eq!(
+            file,
+            File::new(vec!["dir".to_owned(), "file.txt".to_owned()], 234)
+        );
+    }
+
+    #[test]
+    fn safe_path_components() {
+        assert!(File::new(vec!["dir".to_owned(), "file.txt".to_owned()], 1).has_safe_path());
+        assert!(File::new(vec!["..file".to_owned()], 1).has_safe_path());
+
+        for path in [
+            vec![],
+            vec!["".to_owned()],
+            vec![".".to_owned()],
+            vec!["dir".to_owned(), "..".to_owned()],
+            vec!["/etc".to_owned(), "passwd".to_owned()],
+            vec!["../passwd".to_owned()],
+            vec!["dir\\..\\passwd".to_owned()],
+        ] {
+            assert!(!File::new(path.clone(), 1).has_safe_path(), "{path:?}");
+        }
     }
 }
@@ -1,22 +1,33 @@
 use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
 
-use std::io::Read;
+use std::path::Path;
 
 use log::{error, info};
 
+use crate::bencode::bytes;
+use crate::bencode::de::from_bytes_prefix;
 use crate::bencode::decode::{Decoder, DecoderError};
 use crate::bencode::encode::{encode_dict_entry, Encode};
-use crate::bencode::metainfo::{Metainfo, MetainfoError};
-use crate::torrent::file::File;
+use crate::bencode::error::BencodeError;
+use crate::bencode::metainfo::MetainfoError;
+use crate::bencode::ser::to_bytes;
+use crate::torrent::file::{is_safe_component, File};
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Info {
     files: Option<Vec<File>>,
     length: Option<usize>,
     name: String,
+    #[serde(rename = "piece length")]
     piece_length: usize,
+    #[serde(with = "bytes")]
     pieces: Vec<u8>,
+    private: Option<usize>,
+    // Info dictionary as received, it may contain keys not known here that are part of the
+    // info hash nonetheless.
+    #[serde(skip)]
+    raw: Option<Vec<u8>>,
 }
 
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
@@ -25,68 +36,112 @@ pub enum InfoError {
     MetainfoError(#[from] MetainfoError),
     #[error("Error during metainfo decoding")]
     DecoderError(#[from] DecoderError),
+    #[error("Invalid info dictionary: {0}")]
+    Bencode(#[from] BencodeError),
     #[error("No file length specified")]
     NoFileLenght(),
     #[error("No info file found at {0}")]
     NoInfoFile(String),
+    #[error("Unsafe file path {0}")]
+    UnsafePath(String),
+    #[error("Piece length must be greater than zero")]
+    InvalidPieceLength(),
+    #[error("Exactly one of length and files must be given")]
+    InvalidLength(),
+    #[error("Pieces hold {0} bytes instead of {1}")]
+    WrongPiecesLength(usize, usize),
 }
 
 impl Info {
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Info, InfoError> {
-        let decoded_info = Decoder::init(bytes).decode()?;
-        Info::from_metainfo(&decoded_info)
+        let (mut info, parsed_bytes): (Info, usize) = from_bytes_prefix(&bytes)?;
+        info.raw = Some(bytes[..parsed_bytes].to_vec());
+        info.validate()?;
+        Ok(info)
     }
 
-    pub fn from_metainfo(a: &Metainfo) -> Result<Info, InfoError> {
-        let pieces = a.get_bytes_from_dict("pieces")?;
-        let piece_length = a.get_integer_from_dict("piece length")?;
-        let name = a.get_string_from_dict("name")?;
-        let length = a.get_integer_from_dict("length").ok();
+    // Info dictionaries come from uploads and from peers, they are checked before the
+    // torrent is started, so that pieces and paths can be trusted afterwards.
+    fn validate(&self) -> Result<(), InfoError> {
+        if self.piece_length == 0 {
+            return Err(InfoError::InvalidPieceLength());
+        }
+        if self.files.is_some() == self.length.is_some() {
+            return Err(InfoError::InvalidLength());
+        }
+        let expected_pieces = 20 * self.get_piece_count();
+        if self.pieces.len() != expected_pieces {
+            return Err(InfoError::WrongPiecesLength(
+                self.pieces.len(),
+                expected_pieces,
+            ));
+        }
+        if !is_safe_component(&self.name) {
+            return Err(InfoError::UnsafePath(self.name.to_owned()));
+        }
+        if let Some(file) = self
+            .files
+            .iter()
+            .flatten()
+            .find(|file| !file.has_safe_path())
+        {
+            return Err(InfoError::UnsafePath(file.get_path().join("/")));
+        }
 
-        let files = a
-            .get_list_from_dict("files")
-            .ok()
-            .map(|elements| elements.iter().flat_map(File::from_metainfo).collect());
+        Ok(())
+    }
 
-        Ok(Info {
-            name,
-            pieces: pieces.to_vec(),
-            piece_length,
+    // Either files or length is given, for multi-file and single file torrents respectively.
+    pub fn new(
+        name: String,
+        piece_length: usize,
+        pieces: Vec<u8>,
+        files: Option<Vec<File>>,
+        length: Option<usize>,
+        private: bool,
+    ) -> Info {
+        let mut info = Info {
             files,
             length,
-        })
+            name,
+            piece_length,
+            pieces,
+            private: private.then_some(1),
+            raw: None,
+        };
+        info.raw = Some(info.encode());
+        info
     }
 
-    pub fn from_file(file_path: &str) -> Result<Info, InfoError> {
-        if let Ok(mut info_file) = std::fs::File::open(file_path) {
-            info!("Torrent info found in file: {:?}", file_path);
-            let mut info_buffer = "".to_string();
-            if let Err(err) = info_file.read_to_string(&mut info_buffer) {
-                error!(
-                    "Caught error {:?} while reading torrent info from file: {:?}",
-                    err, file_path
-                );
-                return Err(InfoError::NoInfoFile(file_path.to_owned()));
-            }
+    // The info is cached as a .torrent file holding just the info dictionary, so that other
+    // clients are able to load it as well.
+    pub fn from_file(file_path: &Path) -> Result<Info, InfoError> {
+        let bytes = std::fs::read(file_path)
+            .map_err(|_| InfoError::NoInfoFile(file_path.display().to_string()))?;
+        info!("Torrent info found in file: {:?}", file_path);
 
-            let info: Info = serde_json::from_str(&info_buffer).unwrap();
+        let raw_info = Decoder::init(bytes)
+            .get_raw_value_from_dict("info")?
+            .ok_or_else(|| MetainfoError::NoKeyInDictionary("info".to_owned()))?;
 
-            return Ok(info);
-        }
-
-        Err(InfoError::NoInfoFile(file_path.to_owned()))
+        Info::from_bytes(raw_info)
     }
 
-    pub fn save(&self, file_path: &str) -> Result<(), InfoError> {
-        if let Err(err) = serde_json::to_writer(&std::fs::File::create(file_path).unwrap(), self) {
+    pub fn save(&self, file_path: &Path) -> Result<(), InfoError> {
+        let torrent = [
+            "d".as_bytes(),
+            encode_dict_entry("info", self).as_slice(),
+            "e".as_bytes(),
+        ]
+        .concat();
+
+        std::fs::write(file_path, torrent).map_err(|err| {
             error!(
                 "Caught error {:?} while saving the info to file {:?}",
                 err, file_path
             );
-            return Err(InfoError::NoInfoFile(file_path.to_owned()));
-        };
-
-        Ok(())
+            InfoError::NoInfoFile(file_path.display().to_string())
+        })
     }
 
     pub fn get_piece(&self, index: usize) -> &[u8] {
@@ -100,10 +155,31 @@ impl Info {
         Sha1::digest(piece).as_slice() == self.get_piece(piece_idx)
     }
 
+    pub fn get_name(&self) -> String {
+        self.name.to_owned()
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private == Some(1)
+    }
+
     pub fn get_piece_length(&self) -> usize {
         self.piece_length
     }
 
+    // Every piece has the piece length, except the last one that can be shorter.
+    pub fn get_piece_size(&self, index: usize) -> usize {
+        let start = index * self.piece_length;
+        self.piece_length
+            .min(self.get_total_length().saturating_sub(start))
+    }
+
+    pub fn get_piece_count(&self) -> usize {
+        (0..self.get_total_length())
+            .step_by(self.piece_length)
+            .len()
+    }
+
     pub fn get_total_length(&self) -> usize {
         match &self.files {
             Some(files) => files.iter().map(|file| file.get_length()).sum::<usize>(),
@@ -129,22 +205,10 @@ impl Info {
 
 impl Encode for Info {
     fn encode(&self) -> Vec<u8> {
-        let files = encode_dict_entry("files", &self.files);
-        let length = encode_dict_entry("length", &self.length);
-        let name = encode_dict_entry("name", &self.name);
-        let piece_length = encode_dict_entry("piece length", &self.piece_length);
-        let pieces = encode_dict_entry("pieces", &self.pieces);
-
-        [
-            "d".as_bytes(),
-            files.as_slice(),
-            length.as_slice(),
-            name.as_slice(),
-            piece_length.as_slice(),
-            pieces.as_slice(),
-            "e".as_bytes(),
-        ]
-        .concat()
+        match &self.raw {
+            Some(raw) => raw.to_vec(),
+            None => to_bytes(self).expect("Info holds only types supported by bencode"),
+        }
     }
 }
 
@@ -163,6 +227,8 @@ mod test {
             pieces: "ABCDE".as_bytes().to_vec(),
             files: Some(Vec::from([file])),
             length: None,
+            private: None,
+            raw: None,
         };
 
         let expected_hash = "d5:filesld6:lengthi234e4:pathl4:/bineee4:name5:pippo12:piece lengthi43921e6:pieces5:ABCDEe";
@@ -179,6 +245,8 @@ mod test {
             pieces: "ABCDE".as_bytes().to_vec(),
             files: None,
             length: Some(476),
+            private: None,
+            raw: None,
         };
 
         let expected_hash = "d6:lengthi476e4:name5:pippo12:piece lengthi43921e6:pieces5:ABCDEe";
@@ -186,4 +254,120 @@ mod test {
 
         assert_eq!(expected_hash.as_bytes().to_vec(), result_hash);
     }
+
+    #[test]
+    fn piece_sizes() {
+        let info = Info::new("pippo".to_owned(), 4, vec![0; 40], None, Some(10), false);
+
+        assert_eq!(info.get_piece_count(), 3);
+        assert_eq!(info.get_piece_size(0), 4);
+        assert_eq!(info.get_piece_size(2), 2);
+        assert_eq!(info.get_piece_size(3), 0);
+    }
+
+    #[test]
+    fn encode_private_info() {
+        let info = Info::new(
+            "pippo".to_owned(),
+            8,
+            "ABCDEFGHIJKLMNOPQRST".as_bytes().to_vec(),
+            None,
+            Some(5),
+            true,
+        );
+
+        let expected_hash =
+            "d6:lengthi5e4:name5:pippo12:piece lengthi8e6:pieces20:ABCDEFGHIJKLMNOPQRST7:privatei1ee";
+
+        assert_eq!(expected_hash.as_bytes().to_vec(), info.encode());
+        assert_eq!(Info::from_bytes(info.encode()).unwrap(), info);
+    }
+
+    #[test]
+    fn info_hash_with_unknown_keys() {
+        let bytes = "d6:lengthi5e6:md5sum32:0123456789abcdef0123456789abcdef4:name5:pippo12:piece lengthi8e6:pieces20:ABCDEFGHIJKLMNOPQRST6:source3:abce".as_bytes();
+        let info = Info::from_bytes(bytes.to_vec()).unwrap();
+
+        assert_eq!(info.encode(), bytes.to_vec());
+        assert_eq!(
+            info.compute_info_hash(),
+            Sha1::digest(bytes).as_slice().to_vec()
+        );
+    }
+
+    #[test]
+    fn save_and_load_info_file() {
+        let path = std::env::temp_dir().join("rust_bit_info_cache.torrent");
+        let info = Info::new("a".to_owned(), 4, vec![7; 20], None, Some(3), false);
+
+        info.save(&path).unwrap();
+        let saved = std::fs::read(&path).unwrap();
+        assert!(saved.starts_with(b"d4:infod6:lengthi3e"));
+        assert_eq!(Info::from_file(&path).unwrap(), info);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn load_corrupted_info_file() {
+        let path = std::env::temp_dir().join("rust_bit_info_corrupted.torrent");
+        std::fs::write(&path, b"{\"name\": \"json\"}").unwrap();
+
+        assert!(Info::from_file(&path).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reject_unsafe_paths() {
+        let single_file = b"d6:lengthi0e4:name5:../ab12:piece lengthi4e6:pieces0:e";
+        let multi_file =
+            b"d5:filesld6:lengthi0e4:pathl2:..6:passwdeee4:name3:dir12:piece lengthi4e6:pieces0:e";
+
+        assert_eq!(
+            Info::from_bytes(single_file.to_vec()),
+            Err(InfoError::UnsafePath("../ab".to_owned()))
+        );
+        assert_eq!(
+            Info::from_bytes(multi_file.to_vec()),
+            Err(InfoError::UnsafePath("../passwd".to_owned()))
+        );
+    }
+
+    #[test]
+    fn reject_zero_piece_length() {
+        let bytes = b"d6:lengthi5e4:name1:a12:piece lengthi0e6:pieces0:e";
+
+        assert_eq!(
+            Info::from_bytes(bytes.to_vec()),
+            Err(InfoError::InvalidPieceLength())
+        );
+    }
+
+    #[test]
+    fn reject_missing_or_both_lengths() {
+        let neither = b"d4:name1:a12:piece lengthi4e6:pieces0:e";
+        let both =
+            b"d5:filesld6:lengthi0e4:pathl1:beee6:lengthi0e4:name1:a12:piece lengthi4e6:pieces0:e";
+
+        assert_eq!(
+            Info::from_bytes(neither.to_vec()),
+            Err(InfoError::InvalidLength())
+        );
+        assert_eq!(
+            Info::from_bytes(both.to_vec()),
+            Err(InfoError::InvalidLength())
+        );
+    }
+
+    #[test]
+    fn reject_wrong_pieces_length() {
+        // 5 bytes in pieces of 4 bytes are 2 pieces, so 2 hashes are expected.
+        let bytes = b"d6:lengthi5e4:name1:a12:piece lengthi4e6:pieces20:ABCDEFGHIJKLMNOPQRSTe";
+
+        assert_eq!(
+            Info::from_bytes(bytes.to_vec()),
+            Err(InfoError::WrongPiecesLength(20, 40))
+        );
+    }
 }
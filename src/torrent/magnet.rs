@@ -1,75 +1,155 @@
+use std::net::SocketAddr;
+
+use log::info;
+use url::{form_urlencoded, Url};
+
+use crate::common::hex;
+use crate::tracker::peer_endpoint::PeerEndpoint;
+
+const MAGNET_PREFIX: &str = "magnet:?";
+const BTIH_PREFIX: &str = "urn:btih:";
+
+// Magnet link as described by BEP 9, only BitTorrent v1 (btih) info hashes are supported.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Magnet {
     info_hash: Vec<u8>,
+    display_name: Option<String>,
+    exact_length: Option<usize>,
+    trackers: Vec<Url>,
+    web_seeds: Vec<Url>,
+    peers: Vec<PeerEndpoint>,
 }
 
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum MagnetError {
     #[error("No magnet found")]
     NoMagnet(),
+    #[error("No btih info hash found")]
+    NoInfoHash(),
+    #[error("Invalid info hash {0}, expected 40 hex or 32 base32 characters")]
+    InvalidInfoHash(String),
+    #[error("Invalid exact length {0}")]
+    InvalidLength(String),
 }
 
 impl Magnet {
     pub fn get_info_hash(&self) -> Vec<u8> {
         self.info_hash.to_vec()
     }
+
+    pub fn get_display_name(&self) -> Option<String> {
+        self.display_name.clone()
+    }
+
+    pub fn get_exact_length(&self) -> Option<usize> {
+        self.exact_length
+    }
+
+    // Every tracker of the magnet is a tier of its own, tried in the order of the link.
+    pub fn get_tracker_tiers(&self) -> Vec<Vec<Url>> {
+        self.trackers.iter().map(|url| vec![url.clone()]).collect()
+    }
+
+    // Web seeds (BEP 19) are parsed but not downloaded from yet.
+    pub fn get_web_seeds(&self) -> Vec<Url> {
+        self.web_seeds.to_vec()
+    }
+
+    pub fn get_peers(&self) -> Vec<PeerEndpoint> {
+        self.peers.to_vec()
+    }
 }
 
-fn verify_info_hash(magnet_uri: &[u8]) -> bool {
-    magnet_uri.len() < 60 || &magnet_uri[..8] != "magnet:?".as_bytes()
+// The info hash is 40 hex characters or, in older links, 32 base32 characters. Both are
+// decoded to the 20 raw bytes of the SHA-1 digest.
+fn parse_info_hash(value: &str) -> Result<Vec<u8>, MagnetError> {
+    let info_hash = match value.len() {
+        40 => hex::decode(value),
+        32 => base32::decode(base32::Alphabet::RFC4648 { padding: false }, value),
+        _ => None,
+    };
+
+    info_hash
+        .filter(|info_hash| info_hash.len() == 20)
+        .ok_or_else(|| MagnetError::InvalidInfoHash(value.to_owned()))
 }
 
-// Given a string representation of an hex character we obtain the hex byte representation.
-// The hex character A is converted to ascii decimal 65 that correspond to hex decimal
-// 10. It's a simple subtraction in order to project the ascii decimal character to
-// the hex byte representation.
-// hex -> ascii -> hex (byte)
-// A   -> 65    -> 10
-// 9   -> 57    -> 9
-// 8   -> 56    -> 8
-fn byte_to_hex(byte: u8) -> u8 {
-    if byte.is_ascii_uppercase() {
-        byte - 55
-    } else if byte.is_ascii_lowercase() {
-        byte - 87
-    } else {
-        byte - 48
+fn parse_url(value: &str) -> Option<Url> {
+    match Url::parse(value) {
+        Ok(url) => Some(url),
+        Err(err) => {
+            info!("Discarded magnet url {:?} with error {:?}", value, err);
+            None
+        }
     }
 }
 
-fn hex_decode(info_hash_hex_encoded: &[u8]) -> Vec<u8> {
-    info_hash_hex_encoded
-        .chunks_exact(2)
-        .map(|chunk| {
-            let a = byte_to_hex(chunk[0]) << 4;
-            let b = byte_to_hex(chunk[1]);
-            a | b
-        })
-        .collect::<Vec<u8>>()
-}
+// Peer addresses are given as ip:port, with IPv6 addresses enclosed in square brackets.
+// Peers given by hostname are discarded.
+fn parse_peer(value: &str) -> Option<PeerEndpoint> {
+    let peer = value.parse::<SocketAddr>().ok().map(PeerEndpoint::from);
 
-fn parse_info_hash(magnet_uri: &[u8]) -> Vec<u8> {
-    if magnet_uri[60] == b'&' {
-        hex_decode(&magnet_uri[20..60])
-    } else {
-        magnet_uri[20..52].to_vec()
+    if peer.is_none() {
+        info!("Discarded magnet peer {:?}", value);
     }
+
+    peer
 }
 
 pub fn parse_magnet(magnet_uri: Vec<u8>) -> Result<Magnet, MagnetError> {
-    if verify_info_hash(&magnet_uri) {
-        return Err(MagnetError::NoMagnet());
+    let magnet_uri = String::from_utf8(magnet_uri).map_err(|_| MagnetError::NoMagnet())?;
+    let query = magnet_uri
+        .strip_prefix(MAGNET_PREFIX)
+        .ok_or(MagnetError::NoMagnet())?;
+
+    let mut info_hash = None;
+    let mut magnet = Magnet {
+        info_hash: vec![],
+        display_name: None,
+        exact_length: None,
+        trackers: vec![],
+        web_seeds: vec![],
+        peers: vec![],
+    };
+
+    // Values are percent-decoded by the query string parser, unknown keys are ignored.
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "xt" if info_hash.is_none() => {
+                if let Some(hash) = value.strip_prefix(BTIH_PREFIX) {
+                    info_hash = Some(parse_info_hash(hash)?);
+                }
+            }
+            "dn" => magnet.display_name = Some(value.into_owned()),
+            "xl" => {
+                let length = value
+                    .parse()
+                    .map_err(|_| MagnetError::InvalidLength(value.to_string()))?;
+                magnet.exact_length = Some(length);
+            }
+            "tr" => {
+                if let Some(url) = parse_url(&value).filter(|url| !magnet.trackers.contains(url)) {
+                    magnet.trackers.push(url);
+                }
+            }
+            "ws" => magnet.web_seeds.extend(parse_url(&value)),
+            "x.pe" => magnet.peers.extend(parse_peer(&value)),
+            _ => (),
+        }
     }
 
-    let info_hash = parse_info_hash(&magnet_uri);
+    magnet.info_hash = info_hash.ok_or(MagnetError::NoInfoHash())?;
 
-    Ok(Magnet { info_hash })
+    Ok(magnet)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use base32::Alphabet::RFC4648;
+
+    const INFO_HASH: [u8; 20] = [
+        166, 228, 73, 194, 40, 30, 98, 237, 191, 140, 219, 68, 116, 19, 202, 40, 140, 240, 229, 104,
+    ];
 
     #[test]
     fn parse_magnet_hex_encoded_test() {
@@ -79,10 +159,20 @@ mod test {
         assert_eq!(
             result.unwrap(),
             Magnet {
-                info_hash: vec![
-                    166, 228, 73, 194, 40, 30, 98, 237, 191, 140, 219, 68, 116, 19, 202, 40, 140,
-                    240, 229, 104
-                ]
+                info_hash: INFO_HASH.to_vec(),
+                display_name: Some(
+                    "Top.Gun.Maverick.2022.KORSUB.IMAX.1080p.WEBRip.AAC2.0.x264-SHITBOX".to_owned()
+                ),
+                exact_length: None,
+                trackers: vec![
+                    Url::parse("http://tracker.trackerfix.com:80/announce").unwrap(),
+                    Url::parse("udp://9.rarbg.me:2730").unwrap(),
+                    Url::parse("udp://9.rarbg.to:2800").unwrap(),
+                    Url::parse("udp://tracker.tallpenguin.org:15780").unwrap(),
+                    Url::parse("udp://tracker.thinelephant.org:12720").unwrap(),
+                ],
+                web_seeds: vec![],
+                peers: vec![],
             }
         )
     }
@@ -97,14 +187,41 @@ mod test {
             177,
         ];
 
+        assert_eq!(result.unwrap().get_info_hash(), info_hash.to_vec())
+    }
+
+    #[test]
+    fn parse_magnet_info_hash_only_test() {
+        let magnet = "magnet:?xt=urn:btih:a6e449c2281e62edbf8cdb447413ca288cf0e568";
+        let result = parse_magnet(magnet.as_bytes().to_vec()).unwrap();
+
+        assert_eq!(result.get_info_hash(), INFO_HASH.to_vec());
+        assert_eq!(result.get_display_name(), None);
+        assert!(result.get_tracker_tiers().is_empty());
+    }
+
+    #[test]
+    fn parse_magnet_optional_fields_test() {
+        let magnet = "magnet:?dn=Debian+12%20netinst&xl=396361728&xt=urn:btih:a6e449c2281e62edbf8cdb447413ca288cf0e568&ws=http%3A%2F%2Fseed.example.com%2Fdebian.iso&x.pe=10.0.0.1%3A6881&x.pe=%5B2001%3Adb8%3A%3A1%5D%3A51413&x.pe=nohost&tr=udp%3A%2F%2Ftracker%3A80&tr=udp%3A%2F%2Ftracker%3A80";
+        let result = parse_magnet(magnet.as_bytes().to_vec()).unwrap();
+
+        assert_eq!(result.get_info_hash(), INFO_HASH.to_vec());
         assert_eq!(
-            result.unwrap(),
-            Magnet {
-                info_hash: base32::encode(RFC4648 { padding: false }, &info_hash)
-                    .as_bytes()
-                    .to_vec()
-            }
-        )
+            result.get_display_name(),
+            Some("Debian 12 netinst".to_owned())
+        );
+        assert_eq!(result.get_exact_length(), Some(396361728));
+        assert_eq!(
+            result.get_web_seeds(),
+            vec![Url::parse("http://seed.example.com/debian.iso").unwrap()]
+        );
+        assert_eq!(
+            result.get_tracker_tiers(),
+            vec![vec![Url::parse("udp://tracker:80").unwrap()]]
+        );
+
+        let peers: Vec<String> = result.get_peers().iter().map(|p| p.to_string()).collect();
+        assert_eq!(peers, vec!["10.0.0.1:6881", "[2001:db8::1]:51413"]);
     }
 
     #[test]
@@ -112,6 +229,27 @@ mod test {
         let magnet = "magneto:?00000000000000000000000000000000000000000000000000000000";
         let result = parse_magnet(magnet.as_bytes().to_vec());
 
-        assert!(result.is_err())
+        assert_eq!(result, Err(MagnetError::NoMagnet()))
+    }
+
+    #[test]
+    fn parse_magnet_without_info_hash_test() {
+        let magnet = "magnet:?dn=name&xt=urn:sha1:YNCKHTQCWBTRNJIV4WNAE52SJUQCZO5C";
+        let result = parse_magnet(magnet.as_bytes().to_vec());
+
+        assert_eq!(result, Err(MagnetError::NoInfoHash()))
+    }
+
+    #[test]
+    fn parse_magnet_invalid_info_hash_test() {
+        let magnet = "magnet:?xt=urn:btih:a6e449c2281e62edbf8cdb447413ca288cf0e5zz";
+        let result = parse_magnet(magnet.as_bytes().to_vec());
+
+        assert_eq!(
+            result,
+            Err(MagnetError::InvalidInfoHash(
+                "a6e449c2281e62edbf8cdb447413ca288cf0e5zz".to_owned()
+            ))
+        )
     }
 }
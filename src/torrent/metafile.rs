@@ -0,0 +1,298 @@
+use log::info;
+use serde::{Deserialize, Serialize, Serializer};
+use url::Url;
+
+use crate::bencode::de::from_view;
+use crate::bencode::decode::DecoderError;
+use crate::bencode::encode::Encode;
+use crate::bencode::error::BencodeError;
+use crate::bencode::metainfo::MetainfoError;
+use crate::bencode::ser::{to_bytes, Raw};
+use crate::bencode::view::ViewDecoder;
+use crate::common::hex;
+use crate::torrent::info::{Info, InfoError};
+
+// Representation of a .torrent file (the metainfo file of BEP 3), that is the info
+// dictionary together with the trackers to announce to.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Metafile {
+    announce: Option<String>,
+    #[serde(
+        rename = "announce-list",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    announce_list: Vec<Vec<String>>,
+    comment: Option<String>,
+    #[serde(rename = "created by")]
+    created_by: Option<String>,
+    #[serde(rename = "creation date")]
+    creation_date: Option<usize>,
+    #[serde(serialize_with = "serialize_info")]
+    info: Info,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum MetafileError {
+    #[error("Error handling metainfo")]
+    Metainfo(#[from] MetainfoError),
+    #[error("Error during metainfo decoding")]
+    Decoder(#[from] DecoderError),
+    #[error("Invalid metainfo: {0}")]
+    Bencode(#[from] BencodeError),
+    #[error(transparent)]
+    Info(#[from] InfoError),
+}
+
+impl Metafile {
+    // The info is built from its raw bytes, so that the info hash matches the original one even
+    // when the dictionary has keys not known here.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Metafile, MetafileError> {
+        let view = ViewDecoder::new(&bytes)
+            .decode()?
+            .ok_or(BencodeError::NoValue())?;
+        let raw_info = view
+            .get(b"info")
+            .map(|info| info.get_raw().to_vec())
+            .ok_or_else(|| MetainfoError::NoKeyInDictionary("info".to_owned()))?;
+
+        let mut metafile: Metafile = from_view(view)?;
+        metafile.info = Info::from_bytes(raw_info)?;
+        Ok(metafile)
+    }
+
+    // The first tracker of the first tier is also used as announce, for clients that do not
+    // support the announce-list.
+    pub fn new(
+        info: Info,
+        announce_list: Vec<Vec<String>>,
+        comment: Option<String>,
+        created_by: Option<String>,
+        creation_date: Option<usize>,
+    ) -> Metafile {
+        Metafile {
+            announce: announce_list.iter().flatten().next().cloned(),
+            announce_list,
+            comment,
+            created_by,
+            creation_date,
+            info,
+        }
+    }
+
+    pub fn get_info(&self) -> &Info {
+        &self.info
+    }
+
+    pub fn get_info_hash(&self) -> Vec<u8> {
+        self.info.compute_info_hash()
+    }
+
+    // Trackers from the announce-list take precedence over the single announce url,
+    // as suggested by BEP 12. Urls that cannot be parsed are discarded.
+    pub fn get_trackers(&self) -> Vec<Url> {
+        let mut trackers: Vec<Url> = vec![];
+
+        let urls = self
+            .announce_list
+            .iter()
+            .flatten()
+            .chain(self.announce.iter());
+
+        for url in urls {
+            match Url::parse(url) {
+                Ok(url) if !trackers.contains(&url) => trackers.push(url),
+                Ok(_) => (),
+                Err(err) => info!("Discarded tracker {:?} with error {:?}", url, err),
+            }
+        }
+
+        trackers
+    }
+
+    // Tiers of the announce-list (BEP 12). The single announce url is used only when there
+    // is no announce-list.
+    pub fn get_tracker_tiers(&self) -> Vec<Vec<Url>> {
+        let tiers = match self.announce_list.is_empty() {
+            true => self.announce.iter().map(|url| vec![url.clone()]).collect(),
+            false => self.announce_list.clone(),
+        };
+
+        tiers
+            .iter()
+            .map(|tier| {
+                tier.iter()
+                    .filter_map(|url| match Url::parse(url) {
+                        Ok(url) => Some(url),
+                        Err(err) => {
+                            info!("Discarded tracker {:?} with error {:?}", url, err);
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn get_magnet_link(&self) -> String {
+        let mut magnet = format!(
+            "magnet:?xt=urn:btih:{}&dn={}",
+            hex::encode(&self.get_info_hash()),
+            urlencoding::encode(&self.info.get_name())
+        );
+
+        for tracker in self.get_trackers() {
+            magnet.push_str("&tr=");
+            magnet.push_str(&urlencoding::encode(tracker.as_str()));
+        }
+
+        magnet
+    }
+}
+
+impl Encode for Metafile {
+    fn encode(&self) -> Vec<u8> {
+        to_bytes(self).expect("Metafile holds only types supported by bencode")
+    }
+}
+
+// The info is written as encoded by Info, that is as received when it comes from a peer
+// or from a .torrent file.
+fn serialize_info<S: Serializer>(info: &Info, serializer: S) -> Result<S::Ok, S::Error> {
+    Raw(&info.encode()).serialize(serializer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sha1::{Digest, Sha1};
+
+    #[test]
+    fn parse_metafile_with_announce_list() {
+        let bytes = include_bytes!("test_data/fake_prey.torrent").to_vec();
+        let metafile = Metafile::from_bytes(bytes).unwrap();
+
+        assert_eq!(
+            metafile.get_trackers(),
+            vec![
+                Url::parse("http://tracker.trackerfix.com:80/announce").unwrap(),
+                Url::parse("udp://9.rarbg.me:2880/announce").unwrap(),
+                Url::parse("udp://9.rarbg.to:2990/announce").unwrap(),
+                Url::parse("udp://tracker.slowcheetah.org:14750/announce").unwrap(),
+                Url::parse("udp://tracker.tallpenguin.org:15710/announce").unwrap(),
+            ]
+        );
+        assert_eq!(metafile.get_info().get_piece_length(), 8388608);
+        assert_eq!(metafile.get_info().get_total_length(), 5482855764);
+    }
+
+    #[test]
+    fn parse_metafile_with_announce_only() {
+        let bytes = include_bytes!("test_data/fake_debian.torrent").to_vec();
+        let metafile = Metafile::from_bytes(bytes).unwrap();
+
+        assert_eq!(
+            metafile.get_trackers(),
+            vec![Url::parse("http://bttracker.debian.org:6969/announce").unwrap()]
+        );
+        assert_eq!(metafile.get_info().get_total_length(), 396361728);
+        assert_eq!(
+            metafile.get_tracker_tiers(),
+            vec![vec![Url::parse(
+                "http://bttracker.debian.org:6969/announce"
+            )
+            .unwrap()]]
+        );
+    }
+
+    #[test]
+    fn tracker_tiers_from_announce_list() {
+        let info = Info::new("a".to_owned(), 4, vec![0; 20], None, Some(3), false);
+        let metafile = Metafile::new(
+            info,
+            vec![
+                vec!["udp://a:80".to_owned(), "not a url".to_owned()],
+                vec!["udp://b:80".to_owned()],
+            ],
+            None,
+            None,
+            None,
+        );
+
+        assert_eq!(
+            metafile.get_tracker_tiers(),
+            vec![
+                vec![Url::parse("udp://a:80").unwrap()],
+                vec![Url::parse("udp://b:80").unwrap()]
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_metafile() {
+        let info = Info::new("a".to_owned(), 4, vec![0; 20], None, Some(3), false);
+        let metafile = Metafile::new(
+            info,
+            vec![vec!["udp://a:80".to_owned()], vec!["udp://b:80".to_owned()]],
+            Some("test".to_owned()),
+            None,
+            Some(1700000000),
+        );
+
+        let encoded = metafile.encode();
+        let expected = [
+            "d8:announce10:udp://a:8013:announce-listll10:udp://a:80el10:udp://b:80ee".as_bytes(),
+            "7:comment4:test13:creation datei1700000000e".as_bytes(),
+            "4:infod6:lengthi3e4:name1:a12:piece lengthi4e6:pieces20:".as_bytes(),
+            &[0; 20],
+            "ee".as_bytes(),
+        ]
+        .concat();
+
+        assert_eq!(encoded, expected);
+        assert_eq!(Metafile::from_bytes(encoded).unwrap(), metafile);
+    }
+
+    #[test]
+    fn magnet_link_of_metafile() {
+        let bytes = include_bytes!("test_data/fake_debian.torrent").to_vec();
+        let metafile = Metafile::from_bytes(bytes).unwrap();
+
+        let magnet = metafile.get_magnet_link();
+        let parsed = crate::torrent::magnet::parse_magnet(magnet.into_bytes()).unwrap();
+
+        assert_eq!(parsed.get_info_hash(), metafile.get_info_hash());
+        assert_eq!(parsed.get_tracker_tiers(), metafile.get_tracker_tiers());
+        assert_eq!(
+            parsed.get_display_name(),
+            Some(metafile.get_info().get_name())
+        );
+    }
+
+    #[test]
+    fn info_hash_from_original_bytes() {
+        let info =
+            "d6:lengthi3e4:name1:a12:piece lengthi4e6:pieces20:abcdefghijklmnopqrst6:source4:teste";
+        let bytes = format!("d8:announce10:udp://a:804:info{}e", info);
+
+        let metafile = Metafile::from_bytes(bytes.as_bytes().to_vec()).unwrap();
+
+        assert_eq!(
+            metafile.get_info_hash(),
+            Sha1::digest(info.as_bytes()).as_slice().to_vec()
+        );
+        assert_eq!(metafile.encode(), bytes.as_bytes().to_vec());
+    }
+
+    #[test]
+    fn parse_metafile_without_info() {
+        let bytes = b"d8:announce23:http://tracker/announcee".to_vec();
+
+        assert_eq!(
+            Metafile::from_bytes(bytes),
+            Err(MetafileError::Metainfo(MetainfoError::NoKeyInDictionary(
+                "info".to_owned()
+            )))
+        );
+    }
+}
@@ -1,4 +1,6 @@
+pub mod builder;
 pub mod file;
 pub mod info;
 pub mod magnet;
+pub mod metafile;
 pub mod writer;
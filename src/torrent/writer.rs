@@ -1,5 +1,6 @@
 use std::cmp;
 use std::fs;
+use std::path::{Path, PathBuf};
 
 #[cfg(target_family = "unix")]
 use std::os::unix::prelude::FileExt;
@@ -7,25 +8,54 @@ use std::os::unix::prelude::FileExt;
 #[cfg(target_family = "windows")]
 use std::os::windows::prelude::FileExt;
 
-use crate::torrent::file::File;
+use crate::torrent::file::{is_safe_component, File};
 
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
-pub enum WriterError {}
+pub enum WriterError {
+    #[error("Path {0} is outside of the output directory")]
+    OutsideOutputDir(String),
+    #[error("Error writing {0}: {1}")]
+    Io(String, String),
+}
 
 pub fn write(
     piece: Vec<u8>,
     idx: usize,
     files: Vec<File>,
     piece_length: usize,
+    output_dir: &Path,
 ) -> Result<(), WriterError> {
     get_file_writers(files, piece, idx, piece_length)
         .iter()
-        .for_each(|writer| writer.write_to_filesystem());
+        .try_for_each(|writer| writer.write_to_filesystem(output_dir))?;
 
     log::info!("Completed write to filesystem for piece {:?}", idx);
     Ok(())
 }
 
+// Remove the files of a torrent from the filesystem, files never written are ignored.
+pub fn remove(files: &[File], output_dir: &Path) -> std::io::Result<()> {
+    for file in files {
+        let path = resolve_path(&file.get_path(), output_dir)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+        match check_parent_dir(&path, output_dir) {
+            Ok(()) => (),
+            Err(CheckError::NotFound) => continue,
+            Err(CheckError::Outside(err)) => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, err))
+            }
+        }
+
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+            _ => (),
+        }
+    }
+
+    log::info!("Removed {:?} files from filesystem", files.len());
+    Ok(())
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct FileWriter {
     path: Vec<String>,
@@ -44,25 +74,73 @@ impl FileWriter {
         }
     }
 
-    // TODO: Add optional folder where to save output
-    pub fn write_to_filesystem(&self) {
-        let path_string = &self.path.join("/");
-        let path = std::path::Path::new(path_string);
+    pub fn write_to_filesystem(&self, output_dir: &Path) -> Result<(), WriterError> {
+        let path = resolve_path(&self.path, output_dir)?;
+        fs::create_dir_all(output_dir).map_err(|err| io_error(output_dir, err))?;
+        // Nothing is created before the nearest existing directory is known to be in the
+        // output directory.
+        if let Err(CheckError::Outside(err)) = check_parent_dir(&path, output_dir) {
+            return Err(err);
+        }
         let prefix = path.parent().unwrap();
-        std::fs::create_dir_all(prefix).unwrap();
+        fs::create_dir_all(prefix).map_err(|err| io_error(prefix, err))?;
 
         let file = fs::OpenOptions::new()
             .write(true)
             .create(true)
-            .open(path)
-            .unwrap();
+            .truncate(false)
+            .open(&path)
+            .map_err(|err| io_error(&path, err))?;
 
         #[cfg(target_family = "unix")]
-        file.write_at(&self.piece, self.start as u64).unwrap();
+        file.write_at(&self.piece, self.start as u64)
+            .map_err(|err| io_error(&path, err))?;
 
         #[cfg(target_family = "windows")]
-        file.seek_write(&self.piece, self.start as u64).unwrap();
+        file.seek_write(&self.piece, self.start as u64)
+            .map_err(|err| io_error(&path, err))?;
+
+        Ok(())
+    }
+}
+
+enum CheckError {
+    NotFound,
+    Outside(WriterError),
+}
+
+// Paths are checked when the info is parsed, they are checked again here as nothing may be
+// written or removed out of the output directory.
+fn resolve_path(path: &[String], output_dir: &Path) -> Result<PathBuf, WriterError> {
+    if path.is_empty() || !path.iter().all(|component| is_safe_component(component)) {
+        return Err(WriterError::OutsideOutputDir(path.join("/")));
+    }
+
+    Ok(output_dir.join(path.iter().collect::<PathBuf>()))
+}
+
+// The nearest existing directory of the file must still be in the output directory once
+// symlinks are resolved.
+fn check_parent_dir(path: &Path, output_dir: &Path) -> Result<(), CheckError> {
+    let outside = || CheckError::Outside(WriterError::OutsideOutputDir(path.display().to_string()));
+    let parent = path.parent().ok_or_else(outside)?;
+
+    let parent = parent
+        .ancestors()
+        .find_map(|dir| dir.canonicalize().ok())
+        .ok_or(CheckError::NotFound)?;
+    let output_dir = output_dir
+        .canonicalize()
+        .map_err(|_| CheckError::NotFound)?;
+    if !parent.starts_with(output_dir) {
+        return Err(outside());
     }
+
+    Ok(())
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> WriterError {
+    WriterError::Io(path.display().to_string(), err.to_string())
 }
 
 struct FileParser {
@@ -169,8 +247,8 @@ mod test {
         let writer_0 = FileWriter::new(vec!["prova.txt".to_owned()], 0, 6, piece_0);
         let writer_1 = FileWriter::new(vec!["prova.txt".to_owned()], 6, 12, piece_1);
 
-        writer_1.write_to_filesystem();
-        writer_0.write_to_filesystem();
+        writer_1.write_to_filesystem(Path::new(".")).unwrap();
+        writer_0.write_to_filesystem(Path::new(".")).unwrap();
 
         let output = std::fs::read("prova.txt").unwrap();
         assert_eq!(output, vec![0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1]);
@@ -178,6 +256,68 @@ mod test {
         let _ = std::fs::remove_file("prova.txt");
     }
 
+    #[test]
+    fn remove_written_files() {
+        let output_dir = std::env::temp_dir();
+        let writer = FileWriter::new(vec!["remove.txt".to_owned()], 0, 1, vec![0]);
+        writer.write_to_filesystem(&output_dir).unwrap();
+
+        let files = vec![
+            File::new(vec!["remove.txt".to_owned()], 1),
+            File::new(vec!["never_written.txt".to_owned()], 1),
+        ];
+
+        assert!(remove(&files, &output_dir).is_ok());
+        assert!(!output_dir.join("remove.txt").exists());
+    }
+
+    #[test]
+    fn refuse_paths_outside_output_dir() {
+        let output_dir = std::env::temp_dir().join("rust_bit_writer_escape");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let target = std::env::temp_dir().join("rust_bit_writer_target.txt");
+        std::fs::write(&target, b"keep").unwrap();
+
+        let path = vec!["..".to_owned(), "rust_bit_writer_target.txt".to_owned()];
+        let writer = FileWriter::new(path.clone(), 0, 1, vec![0]);
+
+        assert_eq!(
+            writer.write_to_filesystem(&output_dir),
+            Err(WriterError::OutsideOutputDir(
+                "../rust_bit_writer_target.txt".to_owned()
+            ))
+        );
+        assert!(remove(&[File::new(path, 4)], &output_dir).is_err());
+        assert_eq!(std::fs::read(&target).unwrap(), b"keep");
+
+        std::fs::remove_file(target).unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn refuse_symlinks_outside_output_dir() {
+        let output_dir = std::env::temp_dir().join("rust_bit_writer_symlink");
+        let outside = std::env::temp_dir().join("rust_bit_writer_outside");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        let link = output_dir.join("link");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(&outside, &link).unwrap();
+
+        let writer = FileWriter::new(vec!["link".to_owned(), "file".to_owned()], 0, 1, vec![0]);
+
+        assert!(writer.write_to_filesystem(&output_dir).is_err());
+        assert!(!outside.join("file").exists());
+
+        let path = vec!["link".to_owned(), "sub".to_owned(), "file".to_owned()];
+        let writer = FileWriter::new(path, 0, 1, vec![0]);
+
+        assert!(writer.write_to_filesystem(&output_dir).is_err());
+        assert!(!outside.join("sub").exists());
+
+        std::fs::remove_file(link).unwrap();
+    }
+
     #[test]
     fn test_single_file_single_writer() {
         let files = vec![File::new(vec!["path".to_owned()], 32)];
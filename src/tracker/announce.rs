@@ -0,0 +1,456 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::Deserialize;
+use url::Url;
+
+use crate::bencode::bytes;
+use crate::tracker::peer_endpoint::PeerEndpoint;
+
+// Interval used when the tracker does not tell one, as suggested by most trackers.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AnnounceEvent {
+    // Regular announce made at the interval asked by the tracker.
+    #[default]
+    None,
+    Started,
+    Stopped,
+    Completed,
+}
+
+impl AnnounceEvent {
+    // Value of the event parameter of HTTP trackers, the regular announce has none.
+    pub fn as_str(&self) -> Option<&'static str> {
+        match self {
+            AnnounceEvent::None => None,
+            AnnounceEvent::Started => Some("started"),
+            AnnounceEvent::Stopped => Some("stopped"),
+            AnnounceEvent::Completed => Some("completed"),
+        }
+    }
+
+    // Event id used by UDP trackers (BEP 15).
+    pub fn as_id(&self) -> u32 {
+        match self {
+            AnnounceEvent::None => 0,
+            AnnounceEvent::Completed => 1,
+            AnnounceEvent::Started => 2,
+            AnnounceEvent::Stopped => 3,
+        }
+    }
+}
+
+// Everything a client tells the tracker when announcing itself for a torrent (BEP 3).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnnounceRequest {
+    info_hash: Vec<u8>,
+    peer_id: String,
+    port: u16,
+    uploaded: u64,
+    downloaded: u64,
+    left: u64,
+    event: AnnounceEvent,
+    compact: bool,
+    numwant: Option<u32>,
+    key: u32,
+    tracker_id: Option<String>,
+}
+
+impl AnnounceRequest {
+    pub fn new(info_hash: &[u8], peer_id: &str, port: u16) -> AnnounceRequest {
+        AnnounceRequest {
+            info_hash: info_hash.to_vec(),
+            peer_id: peer_id.to_owned(),
+            port,
+            uploaded: 0,
+            downloaded: 0,
+            left: 0,
+            event: AnnounceEvent::None,
+            compact: true,
+            numwant: None,
+            key: rand::random(),
+            tracker_id: None,
+        }
+    }
+
+    pub fn uploaded(mut self, uploaded: u64) -> Self {
+        self.uploaded = uploaded;
+        self
+    }
+
+    pub fn downloaded(mut self, downloaded: u64) -> Self {
+        self.downloaded = downloaded;
+        self
+    }
+
+    pub fn left(mut self, left: u64) -> Self {
+        self.left = left;
+        self
+    }
+
+    pub fn event(mut self, event: AnnounceEvent) -> Self {
+        self.event = event;
+        self
+    }
+
+    // Ask for the peer list in the compact form of BEP 23, or as a dictionary for each peer.
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    pub fn numwant(mut self, numwant: u32) -> Self {
+        self.numwant = Some(numwant);
+        self
+    }
+
+    // Random value that lets the tracker recognize the client when its ip changes, it
+    // has to be the same in every announce of a session.
+    pub fn key(mut self, key: u32) -> Self {
+        self.key = key;
+        self
+    }
+
+    // Tracker id received in a previous response, sent back as it is.
+    pub fn tracker_id(mut self, tracker_id: Option<String>) -> Self {
+        self.tracker_id = tracker_id;
+        self
+    }
+
+    pub fn get_info_hash(&self) -> &[u8] {
+        &self.info_hash
+    }
+
+    pub fn get_peer_id(&self) -> &str {
+        &self.peer_id
+    }
+
+    pub fn get_port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn get_uploaded(&self) -> u64 {
+        self.uploaded
+    }
+
+    pub fn get_downloaded(&self) -> u64 {
+        self.downloaded
+    }
+
+    pub fn get_left(&self) -> u64 {
+        self.left
+    }
+
+    pub fn get_event(&self) -> AnnounceEvent {
+        self.event
+    }
+
+    pub fn get_numwant(&self) -> Option<u32> {
+        self.numwant
+    }
+
+    pub fn get_key(&self) -> u32 {
+        self.key
+    }
+
+    // Announce url of an HTTP tracker, parameters already in the tracker url, such as a
+    // passkey, are kept.
+    pub fn to_url(&self, tracker: &Url) -> String {
+        let mut params = vec![
+            format!("info_hash={}", urlencoding::encode_binary(&self.info_hash)),
+            format!("peer_id={}", urlencoding::encode(&self.peer_id)),
+            format!("port={}", self.port),
+            format!("uploaded={}", self.uploaded),
+            format!("downloaded={}", self.downloaded),
+            format!("left={}", self.left),
+            format!("compact={}", self.compact as u8),
+            format!("key={:08x}", self.key),
+        ];
+        if let Some(event) = self.event.as_str() {
+            params.push(format!("event={event}"));
+        }
+        if let Some(numwant) = self.numwant {
+            params.push(format!("numwant={numwant}"));
+        }
+        if let Some(tracker_id) = &self.tracker_id {
+            params.push(format!("trackerid={}", urlencoding::encode(tracker_id)));
+        }
+
+        let separator = match tracker.query() {
+            Some(_) => '&',
+            None => '?',
+        };
+        format!("{}{}{}", tracker.as_str(), separator, params.join("&"))
+    }
+}
+
+// What the tracker answered, with the peers it gave and when it wants the next announce.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnnounceResponse {
+    interval: Duration,
+    min_interval: Option<Duration>,
+    tracker_id: Option<String>,
+    complete: Option<u32>,
+    incomplete: Option<u32>,
+    warning_message: Option<String>,
+    peers: Vec<PeerEndpoint>,
+}
+
+impl AnnounceResponse {
+    pub fn new(interval: Duration, peers: Vec<PeerEndpoint>) -> AnnounceResponse {
+        AnnounceResponse {
+            interval,
+            min_interval: None,
+            tracker_id: None,
+            complete: None,
+            incomplete: None,
+            warning_message: None,
+            peers,
+        }
+    }
+
+    // Seeders and leechers, as counted by the tracker.
+    pub fn swarm(mut self, complete: u32, incomplete: u32) -> Self {
+        self.complete = Some(complete);
+        self.incomplete = Some(incomplete);
+        self
+    }
+
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+
+    // Announces must not be more frequent than this, unless an event has to be sent.
+    pub fn get_min_interval(&self) -> Option<Duration> {
+        self.min_interval
+    }
+
+    pub fn get_tracker_id(&self) -> Option<&str> {
+        self.tracker_id.as_deref()
+    }
+
+    pub fn get_complete(&self) -> Option<u32> {
+        self.complete
+    }
+
+    pub fn get_incomplete(&self) -> Option<u32> {
+        self.incomplete
+    }
+
+    pub fn get_warning_message(&self) -> Option<&str> {
+        self.warning_message.as_deref()
+    }
+
+    pub fn get_peers(&self) -> &[PeerEndpoint] {
+        &self.peers
+    }
+}
+
+// Bencoded response of HTTP trackers, a failure reason excludes every other key.
+#[derive(Deserialize)]
+pub struct HttpAnnounceResponse {
+    #[serde(rename = "failure reason")]
+    failure_reason: Option<String>,
+    #[serde(rename = "warning message")]
+    warning_message: Option<String>,
+    interval: Option<u64>,
+    #[serde(rename = "min interval")]
+    min_interval: Option<u64>,
+    #[serde(rename = "tracker id")]
+    tracker_id: Option<String>,
+    complete: Option<u32>,
+    incomplete: Option<u32>,
+    #[serde(default)]
+    peers: Peers,
+    // IPv6 peers in compact form (BEP 7).
+    #[serde(default, with = "bytes")]
+    peers6: Vec<u8>,
+}
+
+impl HttpAnnounceResponse {
+    // The failure reason is the error when the tracker refused the announce.
+    pub fn into_response(self) -> Result<AnnounceResponse, String> {
+        if let Some(failure_reason) = self.failure_reason {
+            return Err(failure_reason);
+        }
+        let mut peers = self.peers.0;
+        peers.extend(PeerEndpoint::from_bytes_v6(&self.peers6));
+
+        Ok(AnnounceResponse {
+            interval: self.interval.map_or(DEFAULT_INTERVAL, Duration::from_secs),
+            min_interval: self.min_interval.map(Duration::from_secs),
+            tracker_id: self.tracker_id,
+            complete: self.complete,
+            incomplete: self.incomplete,
+            warning_message: self.warning_message,
+            peers,
+        })
+    }
+}
+
+// Peers are either a compact string of 6 bytes for each peer (BEP 23) or a list of
+// dictionaries with their ip and port (BEP 3). Peers given by hostname are skipped.
+#[derive(Default)]
+struct Peers(Vec<PeerEndpoint>);
+
+#[derive(Deserialize)]
+struct PeerDictionary {
+    ip: String,
+    port: u16,
+}
+
+impl<'de> Deserialize<'de> for Peers {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Peers, D::Error> {
+        deserializer.deserialize_any(PeersVisitor)
+    }
+}
+
+struct PeersVisitor;
+
+impl<'de> Visitor<'de> for PeersVisitor {
+    type Value = Peers;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a compact peer string or a list of peer dictionaries")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Peers, E> {
+        Ok(Peers(PeerEndpoint::from_bytes(bytes)))
+    }
+
+    // Compact peers that happen to be valid UTF-8.
+    fn visit_str<E: de::Error>(self, string: &str) -> Result<Peers, E> {
+        self.visit_bytes(string.as_bytes())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Peers, A::Error> {
+        let mut peers = vec![];
+        while let Some(peer) = seq.next_element::<PeerDictionary>()? {
+            if let Ok(ip) = peer.ip.parse() {
+                peers.push(PeerEndpoint::new(ip, peer.port));
+            }
+        }
+        Ok(Peers(peers))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bencode::de::from_bytes;
+
+    #[test]
+    fn announce_url_with_every_parameter() {
+        let tracker = Url::parse("http://tracker.example.com/announce").unwrap();
+        let request = AnnounceRequest::new(&[0x00, 0xab, b'a'], "-RB0001-abc", 6881)
+            .uploaded(10)
+            .downloaded(20)
+            .left(30)
+            .event(AnnounceEvent::Started)
+            .numwant(50)
+            .key(0xbeef)
+            .tracker_id(Some("id 1".to_owned()));
+
+        assert_eq!(
+            request.to_url(&tracker),
+            "http://tracker.example.com/announce?info_hash=%00%ABa&peer_id=-RB0001-abc&port=6881\
+             &uploaded=10&downloaded=20&left=30&compact=1&key=0000beef&event=started&numwant=50\
+             &trackerid=id%201"
+        );
+    }
+
+    #[test]
+    fn announce_url_keeps_tracker_query() {
+        let tracker = Url::parse("http://tracker.example.com/announce?passkey=abc").unwrap();
+        let url = AnnounceRequest::new(&[0x01], "peer", 1)
+            .key(1)
+            .compact(false)
+            .to_url(&tracker);
+
+        assert!(url.starts_with("http://tracker.example.com/announce?passkey=abc&info_hash=%01"));
+        assert!(url.contains("&compact=0&"));
+        assert!(!url.contains("event="));
+    }
+
+    #[test]
+    fn parse_compact_response() {
+        let bytes = [
+            "d8:completei5e10:incompletei3e8:intervali1800e12:min intervali60e5:peers12:"
+                .as_bytes(),
+            &[10, 0, 0, 1, 0x1a, 0xe1, 192, 168, 1, 2, 0x00, 0x50],
+            "10:tracker id3:abc15:warning message4:slowe".as_bytes(),
+        ]
+        .concat();
+        let response = from_bytes::<HttpAnnounceResponse>(&bytes)
+            .unwrap()
+            .into_response()
+            .unwrap();
+
+        assert_eq!(response.get_interval(), Duration::from_secs(1800));
+        assert_eq!(response.get_min_interval(), Some(Duration::from_secs(60)));
+        assert_eq!(response.get_tracker_id(), Some("abc"));
+        assert_eq!(response.get_complete(), Some(5));
+        assert_eq!(response.get_incomplete(), Some(3));
+        assert_eq!(response.get_warning_message(), Some("slow"));
+        assert_eq!(
+            response.get_peers(),
+            &[
+                PeerEndpoint::new("10.0.0.1".parse().unwrap(), 6881),
+                PeerEndpoint::new("192.168.1.2".parse().unwrap(), 80)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_dictionary_peers() {
+        let bytes = "d8:intervali900e5:peersld2:ip8:10.0.0.17:peer id3:abc4:porti6881eed2:ip3:::14:porti80eed2:ip11:example.com4:porti1eeee";
+        let response = from_bytes::<HttpAnnounceResponse>(bytes.as_bytes())
+            .unwrap()
+            .into_response()
+            .unwrap();
+
+        assert_eq!(response.get_interval(), Duration::from_secs(900));
+        assert_eq!(
+            response.get_peers(),
+            &[
+                PeerEndpoint::new("10.0.0.1".parse().unwrap(), 6881),
+                PeerEndpoint::new("::1".parse().unwrap(), 80)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_ipv6_peers() {
+        let bytes = [
+            "d8:intervali900e5:peers0:6:peers618:".as_bytes(),
+            &[0x20, 0x01, 0x0d, 0xb8],
+            &[0; 11],
+            &[0x01, 0x1a, 0xe1],
+            "e".as_bytes(),
+        ]
+        .concat();
+        let response = from_bytes::<HttpAnnounceResponse>(&bytes)
+            .unwrap()
+            .into_response()
+            .unwrap();
+
+        assert_eq!(
+            response.get_peers(),
+            &[PeerEndpoint::new("2001:db8::1".parse().unwrap(), 6881)]
+        );
+    }
+
+    #[test]
+    fn parse_failure_response() {
+        let response: HttpAnnounceResponse =
+            from_bytes(b"d14:failure reason17:torrent not found8:intervali60ee").unwrap();
+
+        assert_eq!(
+            response.into_response(),
+            Err("torrent not found".to_owned())
+        );
+    }
+}
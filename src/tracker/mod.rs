@@ -1,19 +1,22 @@
+pub mod announce;
 pub mod peer_endpoint;
-mod tcp_tracker;
+pub mod scrape;
+pub mod tcp_tracker;
+pub mod tiers;
 mod udp_tracker;
 
-use crate::common::generator::generate_peer_id;
+use std::time::Duration;
 
-use log::info;
+use log::{info, warn};
 use url::Url;
 
 use self::{
-    peer_endpoint::PeerEndpoint, tcp_tracker::TcpTrackerError, udp_tracker::UdpTrackerError,
+    announce::{AnnounceRequest, AnnounceResponse},
+    scrape::ScrapeResponse,
+    tcp_tracker::{HttpClient, TcpTrackerError},
+    udp_tracker::UdpTrackerError,
 };
 
-#[derive(Debug)]
-pub struct Tracker {}
-
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum TrackerError {
     #[error(transparent)]
@@ -22,23 +25,61 @@ pub enum TrackerError {
     UdpTracker(#[from] UdpTrackerError),
     #[error("Protocol {0} not supported")]
     ProtocolNotSupported(String),
+    #[error("No tracker to announce to")]
+    NoTracker(),
+    #[error("No answer from tracker within {0:?}")]
+    Timeout(Duration),
 }
 
-pub async fn get_peers_by_tracker(
+pub async fn announce(
     tracker: &Url,
-    info_hash: &[u8],
-) -> Result<Vec<PeerEndpoint>, TrackerError> {
-    let peer_id = &generate_peer_id();
+    request: &AnnounceRequest,
+    http: &HttpClient,
+) -> Result<AnnounceResponse, TrackerError> {
+    let response = match tracker.scheme() {
+        "http" | "https" => tcp_tracker::announce(request, tracker, http).await?,
+        "udp" => udp_tracker::announce(request, tracker).await?,
+        scheme => Err(TrackerError::ProtocolNotSupported(scheme.to_string()))?,
+    };
+
+    if let Some(warning) = response.get_warning_message() {
+        warn!("Tracker {:?} warning: {}", tracker.as_str(), warning);
+    }
+    info!(
+        "Found {:?} peers from tracker {:?}",
+        response.get_peers().len(),
+        tracker.as_str()
+    );
 
-    // A tracker response struct with a get_peers method bound to a trait could be useful here ?
+    Ok(response)
+}
+
+// HTTP trackers can be scraped only when the scrape url can be derived from the announce one.
+pub fn supports_scrape(tracker: &Url) -> bool {
+    match tracker.scheme() {
+        "http" | "https" => scrape::scrape_url(tracker).is_some(),
+        "udp" => true,
+        _ => false,
+    }
+}
+
+// Seeders, leechers and completed downloads of many torrents at once.
+pub async fn scrape(
+    tracker: &Url,
+    info_hashes: &[Vec<u8>],
+    http: &HttpClient,
+) -> Result<ScrapeResponse, TrackerError> {
     let response = match tracker.scheme() {
-        "http" => tcp_tracker::call(info_hash, peer_id, tracker)?,
-        "udp" => udp_tracker::call(info_hash, peer_id, tracker).await?,
+        "http" | "https" => tcp_tracker::scrape(info_hashes, tracker, http).await?,
+        "udp" => udp_tracker::scrape(info_hashes, tracker).await?,
         scheme => Err(TrackerError::ProtocolNotSupported(scheme.to_string()))?,
     };
 
-    let peers = PeerEndpoint::from_bytes(&response);
-    info!("Found {:?} peers from tracker {:?}", peers.len(), tracker);
+    info!(
+        "Scraped {:?} torrents from tracker {:?}",
+        response.len(),
+        tracker.as_str()
+    );
 
-    Ok(PeerEndpoint::from_bytes(&response))
+    Ok(response)
 }
@@ -1,42 +1,66 @@
-use std::{net::TcpStream, time::Duration};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
-#[derive(Clone, Debug)]
-pub struct PeerEndpoint(String, u16);
+// Compact peers are the address followed by the port, both in network byte order: 6 bytes
+// for IPv4 (BEP 23) and 18 bytes for IPv6 (BEP 7).
+const COMPACT_V4_SIZE: usize = 6;
+const COMPACT_V6_SIZE: usize = 18;
 
-impl PeerEndpoint {
-    pub fn from_bytes(bytes: &[u8]) -> Vec<Self> {
-        let mut peers_info = Vec::new();
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PeerEndpoint(SocketAddr);
 
-        for chunk in bytes.chunks_exact(6) {
-            let ip = format!(
-                "{:?}.{:?}.{:?}.{:?}",
-                chunk[0], chunk[1], chunk[2], chunk[3]
-            );
-            let port = ((chunk[4] as u16) << 8) | (chunk[5] as u16);
+impl PeerEndpoint {
+    pub fn new(ip: IpAddr, port: u16) -> Self {
+        PeerEndpoint(SocketAddr::new(ip, port))
+    }
 
-            peers_info.push(PeerEndpoint(ip, port))
-        }
+    pub fn from_bytes(bytes: &[u8]) -> Vec<Self> {
+        bytes
+            .chunks_exact(COMPACT_V4_SIZE)
+            .map(|chunk| {
+                let ip = Ipv4Addr::new(chunk[0], chunk[1], chunk[2], chunk[3]);
+                PeerEndpoint::new(ip.into(), u16::from_be_bytes([chunk[4], chunk[5]]))
+            })
+            .collect()
+    }
 
-        peers_info
+    pub fn from_bytes_v6(bytes: &[u8]) -> Vec<Self> {
+        bytes
+            .chunks_exact(COMPACT_V6_SIZE)
+            .map(|chunk| {
+                let ip: [u8; 16] = chunk[..16].try_into().unwrap();
+                PeerEndpoint::new(
+                    Ipv6Addr::from(ip).into(),
+                    u16::from_be_bytes([chunk[16], chunk[17]]),
+                )
+            })
+            .collect()
     }
 
-    pub fn is_reachable(endpoint: &str) -> bool {
-        match endpoint.parse() {
-            Ok(socket) => TcpStream::connect_timeout(&socket, Duration::from_millis(50)).is_ok(),
-            Err(_) => false,
+    // Size of a compact peer for the address family of the tracker, as UDP trackers send
+    // IPv6 peers only to clients announcing over IPv6.
+    pub fn compact_size(ipv6: bool) -> usize {
+        match ipv6 {
+            true => COMPACT_V6_SIZE,
+            false => COMPACT_V4_SIZE,
         }
     }
 
-    pub fn endpoint(&self) -> String {
-        format!("{}:{}", self.ip(), self.port())
+    pub fn endpoint(&self) -> SocketAddr {
+        self.0
     }
+}
 
-    pub fn ip(&self) -> String {
-        self.0.to_string()
+impl From<SocketAddr> for PeerEndpoint {
+    fn from(endpoint: SocketAddr) -> Self {
+        PeerEndpoint(endpoint)
     }
+}
 
-    pub fn port(&self) -> u16 {
-        self.1
+// IPv6 addresses are enclosed in square brackets so that the endpoint can be parsed back.
+impl fmt::Display for PeerEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
     }
 }
 
@@ -54,7 +78,23 @@ mod test {
 
         let peer = peers_endpoint.first().unwrap();
 
-        assert_eq!(peer.ip(), "51.52.48.49");
-        assert_eq!(peer.port(), 12593);
+        assert_eq!(peer.endpoint(), SocketAddr::from(([51, 52, 48, 49], 12593)));
+    }
+
+    #[test]
+    fn test_peers_endpoint_from_bytes_v6() {
+        let bytes = [
+            &[0x20, 0x01, 0x0d, 0xb8][..],
+            &[0; 11],
+            &[0x01, 0x1a, 0xe1],
+            // Truncated entry, shorter than the 18 bytes of a peer, that is ignored.
+            &[0; 5],
+        ]
+        .concat();
+
+        let peers_endpoint = PeerEndpoint::from_bytes_v6(&bytes);
+
+        assert_eq!(peers_endpoint.len(), 1);
+        assert_eq!(peers_endpoint[0].to_string(), "[2001:db8::1]:6881");
     }
 }
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::bencode::bytes;
+
+// Health of the swarm of a torrent, as counted by a tracker.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrapeStats {
+    // Peers with the whole torrent, the seeders.
+    pub complete: u32,
+    // Peers that completed the download since the torrent was registered.
+    pub downloaded: u32,
+    // Peers still downloading, the leechers.
+    pub incomplete: u32,
+}
+
+// Stats by info hash, torrents unknown to the tracker are missing.
+pub type ScrapeResponse = HashMap<Vec<u8>, ScrapeStats>;
+
+// Scrape url of an HTTP tracker: by convention the last path segment of the announce
+// url has to start with "announce", which becomes "scrape". Trackers with other urls do
+// not support scraping.
+pub fn scrape_url(tracker: &Url) -> Option<Url> {
+    let segment = tracker.path_segments()?.next_back()?;
+    let scrape_segment = format!("scrape{}", segment.strip_prefix("announce")?);
+
+    let mut url = tracker.clone();
+    url.path_segments_mut().ok()?.pop().push(&scrape_segment);
+    Some(url)
+}
+
+// One info_hash parameter for every torrent, parameters already in the url are kept.
+pub fn scrape_query(scrape: &Url, info_hashes: &[Vec<u8>]) -> String {
+    let params: Vec<String> = info_hashes
+        .iter()
+        .map(|info_hash| format!("info_hash={}", urlencoding::encode_binary(info_hash)))
+        .collect();
+
+    let separator = match scrape.query() {
+        Some(_) => '&',
+        None => '?',
+    };
+    format!("{}{}{}", scrape.as_str(), separator, params.join("&"))
+}
+
+// Bencoded response of HTTP trackers, the files are keyed by the raw info hash.
+#[derive(Deserialize)]
+pub struct HttpScrapeResponse {
+    #[serde(rename = "failure reason")]
+    failure_reason: Option<String>,
+    #[serde(default)]
+    files: HashMap<InfoHash, ScrapeStats>,
+}
+
+#[derive(Deserialize, PartialEq, Eq, Hash)]
+struct InfoHash(#[serde(with = "bytes")] Vec<u8>);
+
+impl HttpScrapeResponse {
+    // The failure reason is the error when the tracker refused the scrape.
+    pub fn into_response(self) -> Result<ScrapeResponse, String> {
+        if let Some(failure_reason) = self.failure_reason {
+            return Err(failure_reason);
+        }
+
+        Ok(self
+            .files
+            .into_iter()
+            .map(|(info_hash, stats)| (info_hash.0, stats))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bencode::de::from_bytes;
+
+    #[test]
+    fn derive_scrape_url() {
+        let scrape = |url: &str| scrape_url(&Url::parse(url).unwrap()).map(String::from);
+
+        assert_eq!(
+            scrape("http://example.com/announce"),
+            Some("http://example.com/scrape".to_owned())
+        );
+        assert_eq!(
+            scrape("http://example.com/x/announce.php?passkey=1"),
+            Some("http://example.com/x/scrape.php?passkey=1".to_owned())
+        );
+        assert_eq!(
+            scrape("http://example.com/announce_x"),
+            Some("http://example.com/scrape_x".to_owned())
+        );
+        assert_eq!(scrape("http://example.com/a"), None);
+    }
+
+    #[test]
+    fn query_with_many_info_hashes() {
+        let url = Url::parse("http://example.com/scrape?passkey=1").unwrap();
+
+        assert_eq!(
+            scrape_query(&url, &[vec![0x01, b'a'], vec![0xff]]),
+            "http://example.com/scrape?passkey=1&info_hash=%01a&info_hash=%FF"
+        );
+    }
+
+    #[test]
+    fn parse_http_response() {
+        let response: HttpScrapeResponse =
+            from_bytes(b"d5:filesd2:\x01\xffd8:completei5e10:downloadedi50e10:incompletei10eeee")
+                .unwrap();
+
+        assert_eq!(
+            response.into_response(),
+            Ok(HashMap::from([(
+                vec![0x01, 0xff],
+                ScrapeStats {
+                    complete: 5,
+                    downloaded: 50,
+                    incomplete: 10
+                }
+            )]))
+        );
+
+        let response: HttpScrapeResponse =
+            from_bytes(b"d14:failure reason11:not allowede").unwrap();
+        assert_eq!(response.into_response(), Err("not allowed".to_owned()));
+    }
+}
@@ -1,80 +1,404 @@
-use std::io::prelude::*;
 use std::time::Duration;
 
-use crate::bencode::decode::{Decoder, DecoderError};
-use crate::bencode::metainfo::MetainfoError;
+use crate::bencode::de::from_view;
+use crate::bencode::decode::DecoderOptions;
+use crate::bencode::error::BencodeError;
+use crate::bencode::stream::{StreamDecoder, StreamDecoderError};
+use crate::config::TrackersConfig;
+use crate::tracker::announce::{AnnounceRequest, AnnounceResponse, HttpAnnounceResponse};
+use crate::tracker::scrape::{scrape_query, scrape_url, HttpScrapeResponse, ScrapeResponse};
 
-use log::error;
+use serde::de::DeserializeOwned;
 use url::Url;
 
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum TcpTrackerError {
-    #[error("Error handling metainfo")]
-    Metainfo(#[from] MetainfoError),
-    #[error("Error during metainfo decoding")]
-    Decoder(#[from] DecoderError),
-    #[error("Error during connection to tracker {0}")]
-    Connection(String),
+    #[error("Error during tracker response decoding")]
+    Bencode(#[from] BencodeError),
+    #[error("Error during connection to tracker {0}: {1}")]
+    Connection(String, String),
     #[error("Error during reading of buffer")]
     BufferReading(),
+    #[error("Announce refused by the tracker: {0}")]
+    Failure(String),
+    #[error("Tracker {0} does not support scrape")]
+    ScrapeNotSupported(String),
 }
 
-pub fn call(info_hash: &[u8], peer_id: &str, tracker: &Url) -> Result<Vec<u8>, TcpTrackerError> {
-    let url_encoded_info_hash = urlencoding::encode_binary(info_hash).into_owned();
-
-    let url = format!(
-        "{}?info_hash={}&peer_id={}",
-        tracker.as_str(),
-        url_encoded_info_hash,
-        peer_id
-    );
-
-    let response = call_tracker_for_peers(url)?;
-    let mut bytes = vec![];
-    response
-        .into_reader()
-        .read_to_end(&mut bytes)
-        .map_err(|_| TcpTrackerError::BufferReading())?;
-
-    let tracker_metainfo = Decoder::init(bytes).decode()?;
-    Ok(tracker_metainfo.get_bytes_from_dict("peers")?)
+// Tracker responses hold a peer list, anything bigger is refused.
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+
+// Client of the HTTP and HTTPS trackers, shared by every torrent so that connections are
+// kept alive between announces. Redirects are followed and gzip answers decoded.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    agent: ureq::Agent,
+}
+
+impl HttpClient {
+    pub fn new(config: &TrackersConfig) -> HttpClient {
+        HttpClient {
+            agent: HttpClient::builder(config).build(),
+        }
+    }
+
+    fn builder(config: &TrackersConfig) -> ureq::AgentBuilder {
+        ureq::AgentBuilder::new()
+            .user_agent(&config.user_agent)
+            .timeout_connect(Duration::from_secs(config.connect_timeout))
+            .timeout(Duration::from_secs(config.timeout))
+            .redirects(config.max_redirects)
+    }
+
+    // Trusts only the given certificates, for trackers served by the tests.
+    #[cfg(test)]
+    pub fn with_tls_config(
+        config: &TrackersConfig,
+        tls_config: std::sync::Arc<rustls::ClientConfig>,
+    ) -> HttpClient {
+        HttpClient {
+            agent: HttpClient::builder(config).tls_config(tls_config).build(),
+        }
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        HttpClient::new(&TrackersConfig::default())
+    }
 }
 
-fn call_tracker_for_peers(url: String) -> Result<ureq::Response, TcpTrackerError> {
-    ureq::get(&url)
-        .timeout(Duration::from_millis(400))
-        .set("Accept-Encoding", "gzip, deflate, br")
+pub async fn announce(
+    request: &AnnounceRequest,
+    tracker: &Url,
+    http: &HttpClient,
+) -> Result<AnnounceResponse, TcpTrackerError> {
+    let response: HttpAnnounceResponse = get_bencoded(request.to_url(tracker), http).await?;
+    response.into_response().map_err(TcpTrackerError::Failure)
+}
+
+// Every info hash is asked in the same request.
+pub async fn scrape(
+    info_hashes: &[Vec<u8>],
+    tracker: &Url,
+    http: &HttpClient,
+) -> Result<ScrapeResponse, TcpTrackerError> {
+    let scrape = scrape_url(tracker)
+        .ok_or_else(|| TcpTrackerError::ScrapeNotSupported(tracker.to_string()))?;
+
+    let response: HttpScrapeResponse =
+        get_bencoded(scrape_query(&scrape, info_hashes), http).await?;
+    response.into_response().map_err(TcpTrackerError::Failure)
+}
+
+// The request blocks until the tracker answers or the timeout expires, so it is run out of
+// the actors threads.
+async fn get_bencoded<T: DeserializeOwned + Send + 'static>(
+    url: String,
+    http: &HttpClient,
+) -> Result<T, TcpTrackerError> {
+    let agent = http.agent.clone();
+    let task_url = url.clone();
+
+    tokio::task::spawn_blocking(move || get_bencoded_blocking(task_url, &agent))
+        .await
+        .map_err(|err| TcpTrackerError::Connection(url, err.to_string()))?
+}
+
+fn get_bencoded_blocking<T: DeserializeOwned>(
+    url: String,
+    agent: &ureq::Agent,
+) -> Result<T, TcpTrackerError> {
+    let response = call_tracker(url, agent)?;
+    let mut decoder = StreamDecoder::new(response.into_reader())
+        .with_options(DecoderOptions::default().max_size(MAX_RESPONSE_SIZE));
+    let view = match decoder.decode() {
+        Ok(Some(view)) => view,
+        Ok(None) => return Err(BencodeError::NoValue().into()),
+        Err(StreamDecoderError::Io(_)) => return Err(TcpTrackerError::BufferReading()),
+        Err(StreamDecoderError::Decoder(err)) => return Err(BencodeError::from(err).into()),
+    };
+
+    Ok(from_view(view)?)
+}
+
+// The agent asks for gzip answers by itself, and decodes them.
+fn call_tracker(url: String, agent: &ureq::Agent) -> Result<ureq::Response, TcpTrackerError> {
+    agent
+        .get(&url)
         .set("Accept", "*/*")
-        .set("Connection", "keep-alive")
-        .set("User-Agent", "PostmanRuntime/7.29.0")
-        .set("Host", "192.168.1.2")
         .call()
-        .map_err(|_| TcpTrackerError::Connection(url))
+        .map_err(|err| TcpTrackerError::Connection(url, err.to_string()))
 }
 
 #[cfg(test)]
 mod test {
 
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::sync::Arc;
+    use std::thread::JoinHandle;
+
     use super::*;
+    use crate::tracker::announce::AnnounceEvent;
 
+    use flate2::{write::GzEncoder, Compression};
     use httpmock::prelude::*;
+    use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
 
-    #[test]
-    fn test_call() {
+    const CERTIFICATE: &[u8] = include_bytes!("test_data/localhost.crt");
+    const PRIVATE_KEY: &[u8] = include_bytes!("test_data/localhost.key");
+
+    fn crypto_provider() -> Arc<rustls::crypto::CryptoProvider> {
+        Arc::new(rustls::crypto::ring::default_provider())
+    }
+
+    // Answers a single HTTPS request with the given body, the request is returned on join.
+    fn tls_tracker(body: &'static [u8]) -> (SocketAddr, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = rustls::ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from_pem_slice(CERTIFICATE).unwrap()],
+                PrivateKeyDer::from_pem_slice(PRIVATE_KEY).unwrap(),
+            )
+            .unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let connection = rustls::ServerConnection::new(Arc::new(config)).unwrap();
+            let mut tls = rustls::StreamOwned::new(connection, stream);
+
+            let mut request = vec![];
+            let mut buffer = [0; 1024];
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                match tls.read(&mut buffer) {
+                    Ok(0) | Err(_) => return String::new(),
+                    Ok(read) => request.extend_from_slice(&buffer[..read]),
+                }
+            }
+            write!(
+                tls,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            tls.write_all(body).unwrap();
+            tls.conn.send_close_notify();
+            tls.flush().unwrap();
+
+            String::from_utf8_lossy(&request).into_owned()
+        });
+
+        (addr, handle)
+    }
+
+    fn tls_client(config: &TrackersConfig) -> HttpClient {
+        let mut roots = rustls::RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_slice(CERTIFICATE).unwrap())
+            .unwrap();
+        let tls_config = rustls::ClientConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        HttpClient::with_tls_config(config, Arc::new(tls_config))
+    }
+
+    #[actix_web::test]
+    async fn test_announce() {
         let server = MockServer::start();
 
         let url = Url::parse(&server.url("/announce")).unwrap();
 
         let mock = server.mock(|when, then| {
-            when.method(GET).path("/announce");
+            when.method(GET)
+                .path("/announce")
+                .query_param("port", "8000")
+                .query_param("left", "42")
+                .query_param("compact", "1")
+                .query_param("event", "started");
             then.status(200)
                 .header("content-type", "text/html; charset=UTF-8")
-                .body("d5:peers3:baue");
+                .body("d8:intervali60e5:peers6:abcdefe");
         });
 
-        let result = call(&[0x00], "peer_id", &url);
+        let request = AnnounceRequest::new(&[0x00], "peer_id", 8000)
+            .left(42)
+            .event(AnnounceEvent::Started);
+        let result = announce(&request, &url, &HttpClient::default())
+            .await
+            .unwrap();
 
         mock.assert();
-        assert_eq!(result, Ok(vec![98, 97, 117]))
+        assert_eq!(result.get_interval(), Duration::from_secs(60));
+        assert_eq!(result.get_peers().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_announce_failure() {
+        let server = MockServer::start();
+
+        let url = Url::parse(&server.url("/announce")).unwrap();
+
+        server.mock(|when, then| {
+            when.method(GET).path("/announce");
+            then.status(200).body("d14:failure reason11:not allowede");
+        });
+
+        let result = announce(
+            &AnnounceRequest::new(&[0x00], "peer_id", 8000),
+            &url,
+            &HttpClient::default(),
+        )
+        .await;
+
+        assert_eq!(
+            result,
+            Err(TcpTrackerError::Failure("not allowed".to_owned()))
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_scrape() {
+        let server = MockServer::start();
+
+        let url = Url::parse(&server.url("/announce.php?passkey=abc")).unwrap();
+
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/scrape.php")
+                .query_param("passkey", "abc")
+                .query_param("info_hash", "a")
+                .query_param("info_hash", "b");
+            then.status(200)
+                .body("d5:filesd1:ad8:completei1e10:downloadedi2e10:incompletei3eeee");
+        });
+
+        let http = HttpClient::default();
+        let result = scrape(&[b"a".to_vec(), b"b".to_vec()], &url, &http)
+            .await
+            .unwrap();
+
+        mock.assert();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[b"a".as_slice()].incomplete, 3);
+
+        let url = Url::parse(&server.url("/tracker")).unwrap();
+        assert_eq!(
+            scrape(&[b"a".to_vec()], &url, &http).await,
+            Err(TcpTrackerError::ScrapeNotSupported(url.to_string()))
+        );
+    }
+
+    #[actix_web::test]
+    async fn announce_over_tls() {
+        let (addr, tracker) = tls_tracker(b"d8:intervali60e5:peers6:abcdefe");
+        let url = Url::parse(&format!("https://{addr}/announce")).unwrap();
+
+        let result = announce(
+            &AnnounceRequest::new(&[0x00], "peer_id", 8000),
+            &url,
+            &tls_client(&TrackersConfig::default()),
+        )
+        .await
+        .unwrap();
+        let request = tracker.join().unwrap();
+
+        assert_eq!(result.get_peers().len(), 1);
+        assert!(request.starts_with("GET /announce?"));
+        assert!(request.to_lowercase().contains(&format!(
+            "user-agent: rust_bit/{}",
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+
+    #[actix_web::test]
+    async fn announce_to_untrusted_tls_tracker() {
+        let (addr, _tracker) = tls_tracker(b"d8:intervali60e5:peers0:e");
+        let url = Url::parse(&format!("https://{addr}/announce")).unwrap();
+
+        let result = announce(
+            &AnnounceRequest::new(&[0x00], "peer_id", 8000),
+            &url,
+            &HttpClient::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(TcpTrackerError::Connection(_, _))));
+    }
+
+    #[actix_web::test]
+    async fn follow_redirect_to_gzip_answer() {
+        let server = MockServer::start();
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder
+            .write_all(b"d8:intervali60e5:peers6:abcdefe")
+            .unwrap();
+        let body = encoder.finish().unwrap();
+
+        let redirect = server.mock(|when, then| {
+            when.method(GET).path("/announce");
+            then.status(302)
+                .header("location", server.url("/moved/announce?port=8000"));
+        });
+        let moved = server.mock(|when, then| {
+            when.method(GET)
+                .path("/moved/announce")
+                .header("user-agent", "tester/1.0")
+                .header("accept-encoding", "gzip");
+            then.status(200)
+                .header("content-encoding", "gzip")
+                .body(body);
+        });
+
+        let config = TrackersConfig {
+            user_agent: "tester/1.0".to_owned(),
+            ..TrackersConfig::default()
+        };
+        let url = Url::parse(&server.url("/announce")).unwrap();
+        let result = announce(
+            &AnnounceRequest::new(&[0x00], "peer_id", 8000),
+            &url,
+            &HttpClient::new(&config),
+        )
+        .await
+        .unwrap();
+
+        redirect.assert();
+        moved.assert();
+        assert_eq!(result.get_peers().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn slow_tracker_times_out() {
+        let server = MockServer::start();
+
+        server.mock(|when, then| {
+            when.method(GET).path("/announce");
+            then.status(200)
+                .delay(Duration::from_secs(3))
+                .body("d8:intervali60e5:peers0:e");
+        });
+
+        let config = TrackersConfig {
+            timeout: 1,
+            ..TrackersConfig::default()
+        };
+        let url = Url::parse(&server.url("/announce")).unwrap();
+        let result = announce(
+            &AnnounceRequest::new(&[0x00], "peer_id", 8000),
+            &url,
+            &HttpClient::new(&config),
+        )
+        .await;
+
+        assert!(matches!(result, Err(TcpTrackerError::Connection(_, _))));
     }
 }
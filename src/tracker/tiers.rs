@@ -0,0 +1,128 @@
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use url::Url;
+
+// Trackers of a torrent grouped in tiers (BEP 12). Trackers are tried in order, a tier is
+// used only when every tracker of the previous ones failed. The tracker that answers is
+// moved to the front of its tier, so that it is the first one tried next time.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrackerTiers(Vec<Vec<Url>>);
+
+impl TrackerTiers {
+    // Empty tiers and trackers already in a previous tier are dropped.
+    pub fn new(tiers: Vec<Vec<Url>>) -> TrackerTiers {
+        let mut seen: Vec<Url> = vec![];
+        let mut result = vec![];
+
+        for tier in tiers {
+            let mut urls = vec![];
+            for url in tier {
+                if !seen.contains(&url) {
+                    seen.push(url.clone());
+                    urls.push(url);
+                }
+            }
+            if !urls.is_empty() {
+                result.push(urls);
+            }
+        }
+
+        TrackerTiers(result)
+    }
+
+    // Trackers of a tier are shuffled once, when the torrent is added.
+    pub fn shuffle(mut self) -> Self {
+        for tier in self.0.iter_mut() {
+            tier.shuffle(&mut thread_rng());
+        }
+        self
+    }
+
+    // Tier of last resort, e.g. the default trackers of the client.
+    pub fn with_tier(self, tier: Vec<Url>) -> Self {
+        let mut tiers = self.0;
+        tiers.push(tier);
+        TrackerTiers::new(tiers)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Every tracker in the order they are tried.
+    pub fn get_trackers(&self) -> Vec<Url> {
+        self.0.iter().flatten().cloned().collect()
+    }
+
+    pub fn promote(&mut self, url: &Url) {
+        for tier in self.0.iter_mut() {
+            if let Some(position) = tier.iter().position(|tracker| tracker == url) {
+                let tracker = tier.remove(position);
+                tier.insert(0, tracker);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn urls(urls: &[&str]) -> Vec<Url> {
+        urls.iter().map(|url| Url::parse(url).unwrap()).collect()
+    }
+
+    #[test]
+    fn drop_duplicates_and_empty_tiers() {
+        let tiers = TrackerTiers::new(vec![
+            urls(&["udp://a:80", "udp://b:80"]),
+            vec![],
+            urls(&["udp://b:80", "udp://c:80"]),
+        ])
+        .with_tier(urls(&["udp://a:80"]));
+
+        assert_eq!(
+            tiers,
+            TrackerTiers(vec![
+                urls(&["udp://a:80", "udp://b:80"]),
+                urls(&["udp://c:80"])
+            ])
+        );
+        assert_eq!(
+            tiers.get_trackers(),
+            urls(&["udp://a:80", "udp://b:80", "udp://c:80"])
+        );
+    }
+
+    #[test]
+    fn promote_within_its_tier() {
+        let mut tiers = TrackerTiers::new(vec![
+            urls(&["udp://a:80", "udp://b:80"]),
+            urls(&["udp://c:80", "udp://d:80"]),
+        ]);
+        tiers.promote(&Url::parse("udp://d:80").unwrap());
+
+        assert_eq!(
+            tiers.get_trackers(),
+            urls(&["udp://a:80", "udp://b:80", "udp://d:80", "udp://c:80"])
+        );
+    }
+
+    #[test]
+    fn shuffle_keeps_tiers() {
+        let tiers = TrackerTiers::new(vec![
+            urls(&["udp://a:80", "udp://b:80", "udp://c:80"]),
+            urls(&["udp://d:80"]),
+        ])
+        .shuffle();
+
+        let mut first_tier = tiers.0[0].clone();
+        first_tier.sort();
+        assert_eq!(
+            first_tier,
+            urls(&["udp://a:80", "udp://b:80", "udp://c:80"])
+        );
+        assert_eq!(tiers.0[1], urls(&["udp://d:80"]));
+    }
+}
@@ -1,15 +1,62 @@
-use tokio::net::UdpSocket;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use log::debug;
+use tokio::net::{lookup_host, UdpSocket};
+use tokio::time::{timeout_at, Instant};
 
 use url::Url;
 
+use crate::tracker::announce::{AnnounceRequest, AnnounceResponse};
+use crate::tracker::peer_endpoint::PeerEndpoint;
+use crate::tracker::scrape::{ScrapeResponse, ScrapeStats};
+
+// Magic constant that identifies a connect request, BEP 15.
+const PROTOCOL_ID: u64 = 0x41727101980;
+
+const CONNECT_ACTION: u32 = 0;
+const ANNOUNCE_ACTION: u32 = 1;
+const SCRAPE_ACTION: u32 = 2;
+const ERROR_ACTION: u32 = 3;
+
+// Action and transaction id start every response.
+const RESPONSE_HEADER_SIZE: usize = 8;
+const CONNECT_RESPONSE_SIZE: usize = 16;
+// Action, transaction id, interval, leechers and seeders come before the peers.
+const ANNOUNCE_HEADER_SIZE: usize = 20;
+// Seeders, completed and leechers of a torrent in a scrape response.
+const SCRAPE_ENTRY_SIZE: usize = 12;
+// Torrents asked in a single scrape request, so that the response fits in a packet.
+const MAX_SCRAPE_HASHES: usize = 74;
+const MAX_PACKET_SIZE: usize = 4096;
+
+// A connection id can be used for one minute after it was received.
+const CONNECTION_ID_LIFETIME: Duration = Duration::from_secs(60);
+
+// Connection ids received so far, by tracker address.
+static CONNECTIONS: LazyLock<Mutex<HashMap<String, (u64, Instant)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum UdpTrackerError {
     #[error("Tracker missing post")]
     PostMissing(),
     #[error("Tracker missing hostname")]
     HostnameMissing(),
-    #[error("Wrong response size less than 26 bytes")]
-    WrongResponseSize(),
+    #[error("Wrong response size {0} for action {1}")]
+    WrongResponseSize(usize, u32),
+    #[error("Unexpected action {0} in tracker response")]
+    UnexpectedAction(u32),
+    #[error("Transaction id of the response does not match the request")]
+    WrongTransactionId(),
+    #[error("Tracker failure: {0}")]
+    Failure(String),
+    #[error("No answer from udp tracker after {0} retries")]
+    Timeout(u32),
+    #[error("Error in binding the udp socket")]
+    BindError(),
     #[error("No connection established with udp tracker")]
     NoConnectionEstablished(),
     #[error("Error in recieving packet through socket")]
@@ -18,14 +65,115 @@ pub enum UdpTrackerError {
     SendError(),
 }
 
-pub async fn call(
-    info_hash: &[u8],
-    peer_id: &str,
+// Typed reply of the tracker, once its transaction id has been checked.
+#[derive(Debug, PartialEq)]
+enum Response {
+    Connect(u64),
+    Announce(AnnounceResponse),
+    Scrape(Vec<ScrapeStats>),
+    Error(String),
+}
+
+// BEP 15: a request without answer is sent again after 15 * 2 ^ n seconds, with n
+// going from 0 up to 8.
+#[derive(Debug, Clone, Copy)]
+struct Retransmission {
+    timeout: Duration,
+    max_retries: u32,
+}
+
+const RETRANSMISSION: Retransmission = Retransmission {
+    timeout: Duration::from_secs(15),
+    max_retries: 8,
+};
+
+impl Response {
+    fn get_action(&self) -> u32 {
+        match self {
+            Response::Connect(_) => CONNECT_ACTION,
+            Response::Announce(_) => ANNOUNCE_ACTION,
+            Response::Scrape(_) => SCRAPE_ACTION,
+            Response::Error(_) => ERROR_ACTION,
+        }
+    }
+}
+
+impl Retransmission {
+    fn get_timeout(&self, retry: u32) -> Duration {
+        self.timeout * 2_u32.pow(retry)
+    }
+}
+
+pub async fn announce(
+    request: &AnnounceRequest,
     tracker_url: &Url,
-) -> Result<Vec<u8>, UdpTrackerError> {
-    let socket = UdpSocket::bind("0.0.0.0:0")
-        .await
-        .expect("couldn't bind to address");
+) -> Result<AnnounceResponse, UdpTrackerError> {
+    announce_with(request, tracker_url, RETRANSMISSION).await
+}
+
+pub async fn scrape(
+    info_hashes: &[Vec<u8>],
+    tracker_url: &Url,
+) -> Result<ScrapeResponse, UdpTrackerError> {
+    scrape_with(info_hashes, tracker_url, RETRANSMISSION).await
+}
+
+async fn announce_with(
+    request: &AnnounceRequest,
+    tracker_url: &Url,
+    retransmission: Retransmission,
+) -> Result<AnnounceResponse, UdpTrackerError> {
+    let (socket, tracker_hostname) = open_socket(tracker_url).await?;
+
+    let make_message = |transaction_id, connection_id| {
+        make_announce_message(transaction_id, connection_id, request)
+    };
+    match send_request(&socket, &tracker_hostname, make_message, retransmission).await? {
+        Response::Announce(response) => Ok(response),
+        Response::Error(message) => Err(failure(&tracker_hostname, message)),
+        response => Err(UdpTrackerError::UnexpectedAction(response.get_action())),
+    }
+}
+
+// Torrents are scraped in batches, as many as a packet holds.
+async fn scrape_with(
+    info_hashes: &[Vec<u8>],
+    tracker_url: &Url,
+    retransmission: Retransmission,
+) -> Result<ScrapeResponse, UdpTrackerError> {
+    let (socket, tracker_hostname) = open_socket(tracker_url).await?;
+    let mut scraped = ScrapeResponse::new();
+
+    for batch in info_hashes.chunks(MAX_SCRAPE_HASHES) {
+        let make_message = |transaction_id, connection_id| {
+            make_scrape_message(transaction_id, connection_id, batch)
+        };
+        let stats =
+            match send_request(&socket, &tracker_hostname, make_message, retransmission).await? {
+                Response::Scrape(stats) => stats,
+                Response::Error(message) => return Err(failure(&tracker_hostname, message)),
+                response => return Err(UdpTrackerError::UnexpectedAction(response.get_action())),
+            };
+        // Stats come in the same order as the info hashes of the request.
+        if stats.len() != batch.len() {
+            return Err(UdpTrackerError::WrongResponseSize(
+                RESPONSE_HEADER_SIZE + stats.len() * SCRAPE_ENTRY_SIZE,
+                SCRAPE_ACTION,
+            ));
+        }
+        scraped.extend(batch.iter().cloned().zip(stats));
+    }
+
+    Ok(scraped)
+}
+
+// The connection id may be the reason of a failure, a new one is asked next time.
+fn failure(tracker_hostname: &str, message: String) -> UdpTrackerError {
+    CONNECTIONS.lock().unwrap().remove(tracker_hostname);
+    UdpTrackerError::Failure(message)
+}
+
+async fn open_socket(tracker_url: &Url) -> Result<(UdpSocket, String), UdpTrackerError> {
     let tracker_hostname = format!(
         "{}:{}",
         tracker_url
@@ -34,50 +182,185 @@ pub async fn call(
         tracker_url.port().ok_or(UdpTrackerError::PostMissing())?
     );
 
+    let tracker_addr = lookup_host(&tracker_hostname)
+        .await
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or(UdpTrackerError::NoConnectionEstablished())?;
+
+    // The socket has to be of the same address family of the tracker.
+    let local_addr = match tracker_addr {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let socket = UdpSocket::bind(local_addr)
+        .await
+        .map_err(|_| UdpTrackerError::BindError())?;
     socket
-        .connect(tracker_hostname.clone())
+        .connect(tracker_addr)
         .await
-        .expect("Panico");
+        .map_err(|_| UdpTrackerError::NoConnectionEstablished())?;
 
-    let transaction_id: &[u8] = &[0x00, 0x01, 0x19, 0x9e];
-    let connection_id = connect_to_tracker(transaction_id, &socket).await?;
+    Ok((socket, tracker_hostname))
+}
 
-    let message = &make_announce_message(
-        transaction_id,
-        &connection_id,
-        peer_id.as_bytes(),
-        info_hash,
-    );
-    send_upd_packet(&socket, message).await?;
+fn cached_connection_id(tracker_hostname: &str) -> Option<u64> {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    match connections.get(tracker_hostname) {
+        Some((connection_id, received)) if received.elapsed() < CONNECTION_ID_LIFETIME => {
+            Some(*connection_id)
+        }
+        Some(_) => {
+            connections.remove(tracker_hostname);
+            None
+        }
+        None => None,
+    }
+}
+
+// Sends the request until a response with the same transaction id comes back, responses
+// to other transactions are ignored, e.g. late answers to a previous request. A connection
+// id is asked first, and again before any retransmission once it has expired: the retry
+// count goes on across both, as in BEP 15.
+async fn send_request(
+    socket: &UdpSocket,
+    tracker_hostname: &str,
+    make_message: impl Fn(u32, u64) -> Vec<u8>,
+    retransmission: Retransmission,
+) -> Result<Response, UdpTrackerError> {
+    let mut buf = [0x00; MAX_PACKET_SIZE];
+    // Peers are IPv6 when announcing over IPv6.
+    let ipv6 = socket.peer_addr().is_ok_and(|addr| addr.is_ipv6());
+    // The connect has its own transaction id, so that a late duplicate answer to it is not
+    // taken for the answer to the request.
+    let connect_transaction_id = rand::random();
+    let transaction_id = rand::random();
+    let mut retry = 0;
 
-    let mut annouce_buf: [u8; 4000] = [0x00; 4000];
-    let resp_size = read_upd_packet(&socket, &mut annouce_buf).await?;
-    if resp_size > 26 {
-        return Ok(annouce_buf[20..resp_size].to_vec());
+    while retry <= retransmission.max_retries {
+        let (message, expected_id, connecting) = match cached_connection_id(tracker_hostname) {
+            Some(connection_id) => (
+                make_message(transaction_id, connection_id),
+                transaction_id,
+                false,
+            ),
+            None => (
+                make_connection_message(connect_transaction_id),
+                connect_transaction_id,
+                true,
+            ),
+        };
+        send_upd_packet(socket, &message).await?;
+        let deadline = Instant::now() + retransmission.get_timeout(retry);
+
+        match receive_response(socket, &mut buf, expected_id, ipv6, deadline).await? {
+            // The request itself is sent right away with the new connection id.
+            Some(Response::Connect(connection_id)) if connecting => {
+                CONNECTIONS
+                    .lock()
+                    .unwrap()
+                    .insert(tracker_hostname.to_owned(), (connection_id, Instant::now()));
+            }
+            Some(response) => return Ok(response),
+            None => {
+                debug!("No answer from udp tracker, retry number {}", retry + 1);
+                retry += 1;
+            }
+        }
     }
 
-    Err(UdpTrackerError::WrongResponseSize())
+    Err(UdpTrackerError::Timeout(retransmission.max_retries))
 }
 
-async fn connect_to_tracker(
-    transaction_id: &[u8],
+// None when nothing came back for the transaction before the deadline.
+async fn receive_response(
     socket: &UdpSocket,
-) -> Result<Vec<u8>, UdpTrackerError> {
-    let message = &make_connection_message(transaction_id);
+    buf: &mut [u8],
+    transaction_id: u32,
+    ipv6: bool,
+    deadline: Instant,
+) -> Result<Option<Response>, UdpTrackerError> {
+    while let Ok(resp_size) = timeout_at(deadline, read_upd_packet(socket, buf)).await {
+        match parse_response(&buf[..resp_size?], transaction_id, ipv6) {
+            Err(UdpTrackerError::WrongTransactionId()) => {
+                debug!("Ignored udp tracker response of another transaction")
+            }
+            response => return response.map(Some),
+        }
+    }
 
-    let mut buf: [u8; 16] = [0x00; 16];
+    Ok(None)
+}
 
-    for retry in 1..5 {
-        if send_upd_packet(socket, message).await.is_err() {
-            log::error!("Send udp connect packet failed, retry number {}", retry);
-        };
+fn parse_response(
+    bytes: &[u8],
+    transaction_id: u32,
+    ipv6: bool,
+) -> Result<Response, UdpTrackerError> {
+    if bytes.len() < RESPONSE_HEADER_SIZE {
+        return Err(UdpTrackerError::WrongResponseSize(bytes.len(), 0));
+    }
+    if read_u32(bytes, 4) != transaction_id {
+        return Err(UdpTrackerError::WrongTransactionId());
+    }
 
-        if read_upd_packet(socket, &mut buf).await.is_ok() {
-            return Ok(buf[8..].to_vec());
-        }
+    match read_u32(bytes, 0) {
+        CONNECT_ACTION if bytes.len() >= CONNECT_RESPONSE_SIZE => Ok(Response::Connect(
+            u64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+        )),
+        ANNOUNCE_ACTION => parse_announce_response(bytes, ipv6).map(Response::Announce),
+        SCRAPE_ACTION => parse_scrape_response(bytes).map(Response::Scrape),
+        ERROR_ACTION => Ok(Response::Error(
+            String::from_utf8_lossy(&bytes[RESPONSE_HEADER_SIZE..]).into_owned(),
+        )),
+        CONNECT_ACTION => Err(UdpTrackerError::WrongResponseSize(
+            bytes.len(),
+            CONNECT_ACTION,
+        )),
+        action => Err(UdpTrackerError::UnexpectedAction(action)),
+    }
+}
+
+fn parse_announce_response(bytes: &[u8], ipv6: bool) -> Result<AnnounceResponse, UdpTrackerError> {
+    if bytes.len() < ANNOUNCE_HEADER_SIZE
+        || !(bytes.len() - ANNOUNCE_HEADER_SIZE).is_multiple_of(PeerEndpoint::compact_size(ipv6))
+    {
+        return Err(UdpTrackerError::WrongResponseSize(
+            bytes.len(),
+            ANNOUNCE_ACTION,
+        ));
     }
 
-    Err(UdpTrackerError::NoConnectionEstablished())
+    let interval = Duration::from_secs(read_u32(bytes, 8) as u64);
+    let peers = match ipv6 {
+        true => PeerEndpoint::from_bytes_v6(&bytes[ANNOUNCE_HEADER_SIZE..]),
+        false => PeerEndpoint::from_bytes(&bytes[ANNOUNCE_HEADER_SIZE..]),
+    };
+
+    Ok(AnnounceResponse::new(interval, peers).swarm(read_u32(bytes, 16), read_u32(bytes, 12)))
+}
+
+fn parse_scrape_response(bytes: &[u8]) -> Result<Vec<ScrapeStats>, UdpTrackerError> {
+    let entries = &bytes[RESPONSE_HEADER_SIZE..];
+    if !entries.len().is_multiple_of(SCRAPE_ENTRY_SIZE) {
+        return Err(UdpTrackerError::WrongResponseSize(
+            bytes.len(),
+            SCRAPE_ACTION,
+        ));
+    }
+
+    Ok(entries
+        .chunks_exact(SCRAPE_ENTRY_SIZE)
+        .map(|entry| ScrapeStats {
+            complete: read_u32(entry, 0),
+            downloaded: read_u32(entry, 4),
+            incomplete: read_u32(entry, 8),
+        })
+        .collect())
+}
+
+fn read_u32(bytes: &[u8], start: usize) -> u32 {
+    u32::from_be_bytes(bytes[start..start + 4].try_into().unwrap())
 }
 
 async fn read_upd_packet(socket: &UdpSocket, buffer: &mut [u8]) -> Result<usize, UdpTrackerError> {
@@ -96,45 +379,356 @@ async fn send_upd_packet(socket: &UdpSocket, message: &[u8]) -> Result<(), UdpTr
     }
 }
 
-fn make_connection_message(transaction_id: &[u8]) -> Vec<u8> {
-    let connection_id: &[u8] = &[0x00, 0x00, 0x04, 0x17, 0x27, 0x10, 0x19, 0x80];
-    let action: &[u8] = &[0x00, 0x00, 0x00, 0x00];
-
-    [connection_id, action, transaction_id].concat().to_vec()
+fn make_connection_message(transaction_id: u32) -> Vec<u8> {
+    [
+        &PROTOCOL_ID.to_be_bytes()[..],
+        &CONNECT_ACTION.to_be_bytes(),
+        &transaction_id.to_be_bytes(),
+    ]
+    .concat()
 }
 
 fn make_announce_message(
-    transaction_id: &[u8],
-    connection_id: &[u8],
-    peer_id: &[u8],
-    info_hash: &[u8],
+    transaction_id: u32,
+    connection_id: u64,
+    request: &AnnounceRequest,
 ) -> Vec<u8> {
-    let action = &1_u32.to_be_bytes();
-    // let action: &[u8] = &[0x00, 0x00, 0x00, 0x01];
-    let downloaded: &[u8] = &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
-    let left: &[u8] = &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
-    let uploaded: &[u8] = &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
-    let event: &[u8] = &[0x00, 0x00, 0x00, 0x00];
     let ip: &[u8] = &[0x00, 0x00, 0x00, 0x00];
-    let key: &[u8] = &[0x00, 0x00, 0x00, 0x00];
-    let num_want: &[u8] = &[0xff, 0xff, 0xff, 0xff]; // -1
-    let port: &[u8] = &[0x1f, 0x40]; // 8000
+    // -1 lets the tracker choose how many peers to send.
+    let num_want = request.get_numwant().map_or(-1, |numwant| numwant as i32);
 
     [
-        connection_id,
-        action,
-        transaction_id,
-        info_hash,
-        peer_id,
-        downloaded,
-        left,
-        uploaded,
-        event,
+        &connection_id.to_be_bytes()[..],
+        &ANNOUNCE_ACTION.to_be_bytes(),
+        &transaction_id.to_be_bytes(),
+        request.get_info_hash(),
+        request.get_peer_id().as_bytes(),
+        &request.get_downloaded().to_be_bytes(),
+        &request.get_left().to_be_bytes(),
+        &request.get_uploaded().to_be_bytes(),
+        &request.get_event().as_id().to_be_bytes(),
         ip,
-        key,
-        num_want,
-        port,
+        &request.get_key().to_be_bytes(),
+        &num_want.to_be_bytes(),
+        &request.get_port().to_be_bytes(),
     ]
     .concat()
-    .to_vec()
+}
+
+fn make_scrape_message(
+    transaction_id: u32,
+    connection_id: u64,
+    info_hashes: &[Vec<u8>],
+) -> Vec<u8> {
+    [
+        &connection_id.to_be_bytes()[..],
+        &SCRAPE_ACTION.to_be_bytes(),
+        &transaction_id.to_be_bytes(),
+        &info_hashes.concat(),
+    ]
+    .concat()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tracker::announce::AnnounceEvent;
+
+    use std::net::Ipv6Addr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const FAST: Retransmission = Retransmission {
+        timeout: Duration::from_millis(20),
+        max_retries: 2,
+    };
+
+    // Fake tracker sending back the replies returned for every request it receives.
+    async fn fake_tracker(mut answer: impl FnMut(&[u8]) -> Vec<Vec<u8>> + Send + 'static) -> Url {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("udp://{}", socket.local_addr().unwrap())).unwrap();
+
+        actix_web::rt::spawn(async move {
+            let mut buf = [0x00; MAX_PACKET_SIZE];
+            while let Ok((size, from)) = socket.recv_from(&mut buf).await {
+                for reply in answer(&buf[..size]) {
+                    socket.send_to(&reply, from).await.unwrap();
+                }
+            }
+        });
+        url
+    }
+
+    fn reply(action: u32, request: &[u8], body: &[u8]) -> Vec<u8> {
+        [&action.to_be_bytes()[..], &request[12..16], body].concat()
+    }
+
+    fn request() -> AnnounceRequest {
+        AnnounceRequest::new(&[0xaa; 20], "-RB0001-abcdefghijkl", 6881)
+    }
+
+    #[test]
+    fn announce_message_layout() {
+        let request = request()
+            .downloaded(1)
+            .left(2)
+            .uploaded(3)
+            .event(AnnounceEvent::Completed)
+            .key(4);
+        let message = make_announce_message(9, 0x11, &request);
+
+        assert_eq!(message.len(), 98);
+        assert_eq!(&message[..8], &0x11_u64.to_be_bytes());
+        assert_eq!(&message[8..16], &[0, 0, 0, 1, 0, 0, 0, 9]);
+        assert_eq!(&message[16..36], &[0xaa; 20]);
+        assert_eq!(&message[56..64], &1_u64.to_be_bytes());
+        assert_eq!(&message[64..72], &2_u64.to_be_bytes());
+        assert_eq!(&message[72..80], &3_u64.to_be_bytes());
+        assert_eq!(&message[80..84], &1_u32.to_be_bytes());
+        assert_eq!(&message[88..92], &4_u32.to_be_bytes());
+        assert_eq!(&message[92..96], &[0xff; 4]);
+        assert_eq!(&message[96..], &6881_u16.to_be_bytes());
+    }
+
+    #[test]
+    fn connection_message_layout() {
+        assert_eq!(
+            make_connection_message(9),
+            [0, 0, 0x04, 0x17, 0x27, 0x10, 0x19, 0x80, 0, 0, 0, 0, 0, 0, 0, 9]
+        );
+    }
+
+    #[test]
+    fn parse_announce() {
+        let bytes = [
+            &[0, 0, 0, 1, 0, 0, 0, 9][..],
+            &1800_u32.to_be_bytes(),
+            &3_u32.to_be_bytes(),
+            &5_u32.to_be_bytes(),
+            &[10, 0, 0, 1, 0x1a, 0xe1],
+        ]
+        .concat();
+        let Ok(Response::Announce(response)) = parse_response(&bytes, 9, false) else {
+            panic!("Expected an announce response");
+        };
+
+        assert_eq!(response.get_interval(), Duration::from_secs(1800));
+        assert_eq!(response.get_complete(), Some(5));
+        assert_eq!(response.get_incomplete(), Some(3));
+        assert_eq!(
+            response.get_peers(),
+            &[PeerEndpoint::new("10.0.0.1".parse().unwrap(), 6881)]
+        );
+
+        assert_eq!(
+            parse_response(&bytes, 10, false),
+            Err(UdpTrackerError::WrongTransactionId())
+        );
+        assert_eq!(
+            parse_response(&bytes[..19], 9, false),
+            Err(UdpTrackerError::WrongResponseSize(19, 1))
+        );
+        assert_eq!(
+            parse_response(&bytes[..25], 9, false),
+            Err(UdpTrackerError::WrongResponseSize(25, 1))
+        );
+    }
+
+    #[test]
+    fn parse_ipv6_announce() {
+        let bytes = [
+            &[0, 0, 0, 1, 0, 0, 0, 9][..],
+            &[0; 12],
+            &Ipv6Addr::LOCALHOST.octets(),
+            &[0x1a, 0xe1],
+        ]
+        .concat();
+        let Ok(Response::Announce(response)) = parse_response(&bytes, 9, true) else {
+            panic!("Expected an announce response");
+        };
+
+        assert_eq!(
+            response.get_peers(),
+            &[PeerEndpoint::new("::1".parse().unwrap(), 6881)]
+        );
+        assert_eq!(
+            parse_response(&bytes[..36], 9, true),
+            Err(UdpTrackerError::WrongResponseSize(36, 1))
+        );
+    }
+
+    #[test]
+    fn parse_connect_and_error() {
+        let connect = [&[0, 0, 0, 0, 0, 0, 0, 9][..], &7_u64.to_be_bytes()].concat();
+        assert_eq!(parse_response(&connect, 9, false), Ok(Response::Connect(7)));
+        assert_eq!(
+            parse_response(&connect[..12], 9, false),
+            Err(UdpTrackerError::WrongResponseSize(12, 0))
+        );
+
+        let error = [&[0, 0, 0, 3, 0, 0, 0, 9][..], b"banned"].concat();
+        assert_eq!(
+            parse_response(&error, 9, false),
+            Ok(Response::Error("banned".to_owned()))
+        );
+
+        let unknown = [0, 0, 0, 4, 0, 0, 0, 9];
+        assert_eq!(
+            parse_response(&unknown, 9, false),
+            Err(UdpTrackerError::UnexpectedAction(4))
+        );
+    }
+
+    #[actix_web::test]
+    async fn announce_reusing_connection_id() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let counter = connects.clone();
+        let url = fake_tracker(move |request| match read_u32(request, 8) {
+            CONNECT_ACTION => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut stale = reply(CONNECT_ACTION, request, &1_u64.to_be_bytes());
+                stale[7] ^= 0xff;
+                vec![
+                    stale,
+                    reply(CONNECT_ACTION, request, &0x42_u64.to_be_bytes()),
+                ]
+            }
+            _ => {
+                assert_eq!(&request[..8], &0x42_u64.to_be_bytes());
+                let body = [
+                    &60_u32.to_be_bytes()[..],
+                    &1_u32.to_be_bytes(),
+                    &2_u32.to_be_bytes(),
+                    &[127, 0, 0, 1, 0x1a, 0xe1],
+                ]
+                .concat();
+                vec![reply(ANNOUNCE_ACTION, request, &body)]
+            }
+        })
+        .await;
+
+        let response = announce_with(&request(), &url, FAST).await.unwrap();
+        announce_with(&request(), &url, FAST).await.unwrap();
+
+        assert_eq!(response.get_interval(), Duration::from_secs(60));
+        assert_eq!(response.get_complete(), Some(2));
+        assert_eq!(response.get_peers().len(), 1);
+        assert_eq!(connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn ignore_duplicate_connect_answer() {
+        let url = fake_tracker(|request| match read_u32(request, 8) {
+            CONNECT_ACTION => {
+                let answer = reply(CONNECT_ACTION, request, &1_u64.to_be_bytes());
+                vec![answer.clone(), answer]
+            }
+            _ => vec![reply(ANNOUNCE_ACTION, request, &[0; 12])],
+        })
+        .await;
+
+        assert!(announce_with(&request(), &url, FAST).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn scrape_in_batches() {
+        let url = fake_tracker(|request| match read_u32(request, 8) {
+            CONNECT_ACTION => vec![reply(CONNECT_ACTION, request, &1_u64.to_be_bytes())],
+            _ => {
+                // Every torrent has as many seeders as the first byte of its info hash.
+                let body: Vec<u8> = request[16..]
+                    .chunks(20)
+                    .flat_map(|info_hash| [info_hash[0] as u32, 0, 1])
+                    .flat_map(u32::to_be_bytes)
+                    .collect();
+                vec![reply(SCRAPE_ACTION, request, &body)]
+            }
+        })
+        .await;
+
+        let info_hashes: Vec<Vec<u8>> = (0..100).map(|idx| vec![idx; 20]).collect();
+        let scraped = scrape_with(&info_hashes, &url, FAST).await.unwrap();
+
+        assert_eq!(scraped.len(), 100);
+        assert_eq!(
+            scraped[&vec![80; 20]],
+            ScrapeStats {
+                complete: 80,
+                downloaded: 0,
+                incomplete: 1
+            }
+        );
+    }
+
+    #[actix_web::test]
+    async fn retransmit_unanswered_request() {
+        let mut received = 0;
+        let url = fake_tracker(move |request| {
+            received += 1;
+            match (received, read_u32(request, 8)) {
+                (1, _) => vec![],
+                (_, CONNECT_ACTION) => {
+                    vec![reply(CONNECT_ACTION, request, &1_u64.to_be_bytes())]
+                }
+                _ => vec![reply(ANNOUNCE_ACTION, request, &[0; 12])],
+            }
+        })
+        .await;
+
+        assert!(announce_with(&request(), &url, FAST).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn reconnect_when_connection_id_expires_between_retransmissions() {
+        let mut connection_id = 0x50_u64;
+        let mut announces = 0;
+        // Only the connection of this tracker is aged, other tests run alongside.
+        let tracker_hostname = Arc::new(Mutex::new(String::new()));
+        let hostname = tracker_hostname.clone();
+        let url = fake_tracker(move |request| match read_u32(request, 8) {
+            CONNECT_ACTION => {
+                connection_id += 1;
+                vec![reply(CONNECT_ACTION, request, &connection_id.to_be_bytes())]
+            }
+            _ => {
+                announces += 1;
+                if announces == 1 {
+                    // The first announce stays unanswered until the connection id is too old.
+                    let mut connections = CONNECTIONS.lock().unwrap();
+                    let (_, received) = connections
+                        .get_mut(hostname.lock().unwrap().as_str())
+                        .unwrap();
+                    *received = Instant::now().checked_sub(CONNECTION_ID_LIFETIME).unwrap();
+                    return vec![];
+                }
+                assert_eq!(&request[..8], &0x52_u64.to_be_bytes());
+                vec![reply(ANNOUNCE_ACTION, request, &[0; 12])]
+            }
+        })
+        .await;
+        *tracker_hostname.lock().unwrap() =
+            format!("{}:{}", url.host_str().unwrap(), url.port().unwrap());
+
+        assert!(announce_with(&request(), &url, FAST).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn error_action() {
+        let url = fake_tracker(|request| vec![reply(ERROR_ACTION, request, b"banned")]).await;
+
+        assert_eq!(
+            announce_with(&request(), &url, FAST).await,
+            Err(UdpTrackerError::Failure("banned".to_owned()))
+        );
+    }
+
+    #[actix_web::test]
+    async fn silent_tracker_times_out() {
+        let url = fake_tracker(|_| vec![]).await;
+
+        assert_eq!(
+            announce_with(&request(), &url, FAST).await,
+            Err(UdpTrackerError::Timeout(2))
+        );
+    }
 }